@@ -0,0 +1,102 @@
+//! `--compact-badges`: the rows of status badges at the top of a README,
+//! shown as one line of `[label: value]` chips instead of image markup.
+
+use pulldown_cmark::{CowStr, Event, Tag, TagEnd};
+
+/// Whether an image URL looks like a status badge.
+fn is_badge(url: &str) -> bool {
+    let url = url.to_ascii_lowercase();
+    let path = url.split(['?', '#']).next().unwrap_or_default();
+    url.contains("shields.io/") || url.contains("badgen.net/") || path.ends_with("badge.svg") || path.contains("/badge/")
+}
+
+/// The message of a static shields.io badge, from its
+/// `/badge/LABEL-MESSAGE-COLOR` path, with its escapes undone.
+fn shields_message(url: &str) -> Option<String> {
+    let (_, badge) = url.split_once("shields.io/badge/")?;
+    let badge = badge.split(['?', '#', '.']).next()?;
+    // `--` is a literal dash and `__` a literal underscore; `_` is a space
+    let parts: Vec<String> = badge
+        .replace("--", "\u{0}")
+        .split('-')
+        .map(|part| part.replace("__", "\u{1}").replace('_', " ").replace('\u{0}', "-").replace('\u{1}', "_"))
+        .collect();
+    match parts.as_slice() {
+        [_, message, _] | [message, _] => Some(message.replace("%20", " ")),
+        _ => None,
+    }
+}
+
+/// The chip for a badge with alt text `alt`: `[License: MIT]` when the alt
+/// text or a static badge's URL gives a value, `[alt]` otherwise.
+fn chip(alt: &str, url: &str) -> String {
+    let alt = alt.trim();
+    if let Some((label, value)) = alt.split_once(':')
+        && !value.trim().is_empty()
+    {
+        return format!("[{}: {}]", label.trim(), value.trim());
+    }
+    match shields_message(url) {
+        Some(message) if !alt.eq_ignore_ascii_case(&message) => format!("[{}: {}]", alt, message),
+        _ => format!("[{}]", alt),
+    }
+}
+
+/// The chips of a paragraph made only of badges, optionally linked, and the
+/// number of events the paragraph takes up.
+fn badge_paragraph(events: &[Event]) -> Option<(Vec<String>, usize)> {
+    let mut events = events.iter().enumerate();
+    let (_, Event::Start(Tag::Paragraph)) = events.next()? else {
+        return None;
+    };
+    let mut chips = Vec::new();
+    let mut image: Option<(String, &str)> = None; // Alt text so far, URL
+    let mut linked = false;
+    for (i, event) in events {
+        match event {
+            Event::Start(Tag::Link { .. }) if !linked && image.is_none() => linked = true,
+            Event::End(TagEnd::Link) if linked && image.is_none() => linked = false,
+            Event::Start(Tag::Image { dest_url, .. }) if image.is_none() && is_badge(dest_url) => {
+                image = Some((String::new(), dest_url));
+            }
+            Event::End(TagEnd::Image) => {
+                let (alt, url) = image.take()?;
+                chips.push(chip(&alt, url));
+            }
+            Event::Text(text) | Event::Code(text) if image.is_some() => {
+                if let Some((alt, _)) = &mut image {
+                    alt.push_str(text);
+                }
+            }
+            Event::Text(text) if text.trim().is_empty() && !linked => {}
+            Event::SoftBreak | Event::HardBreak if image.is_none() && !linked => {}
+            Event::End(TagEnd::Paragraph) if !chips.is_empty() => return Some((chips, i + 1)),
+            _ => return None,
+        }
+    }
+    None
+}
+
+/// The events with the badge paragraphs at the start of the document, after
+/// any title heading, merged into one paragraph of chips. Documents that
+/// don't open that way are left as they are.
+pub fn compact(mut events: Vec<Event>) -> Vec<Event> {
+    let mut start = 0;
+    while let Some(Event::Start(Tag::Heading { .. })) = events.get(start) {
+        match events[start..].iter().position(|event| matches!(event, Event::End(TagEnd::Heading(_)))) {
+            Some(end) => start += end + 1,
+            None => return events,
+        }
+    }
+    let mut chips = Vec::new();
+    let mut end = start;
+    while let Some((paragraph, len)) = badge_paragraph(&events[end..]) {
+        chips.extend(paragraph);
+        end += len;
+    }
+    if !chips.is_empty() {
+        let line = Event::Text(CowStr::from(chips.join(" ")));
+        events.splice(start..end, [Event::Start(Tag::Paragraph), line, Event::End(TagEnd::Paragraph)]);
+    }
+    events
+}
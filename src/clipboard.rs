@@ -0,0 +1,17 @@
+//! `--clipboard`: put the rendered text on the system clipboard.
+
+use std::io::{self, Write};
+
+/// Copy `text` to the clipboard. Without a clipboard (a headless session, no
+/// display server) this is reported on stderr rather than failing the run,
+/// and with `--clipboard-only` the text is printed instead so it isn't lost.
+pub fn copy(text: &str, clipboard_only: bool) -> io::Result<()> {
+    let copied = arboard::Clipboard::new().and_then(|mut clipboard| clipboard.set_text(text));
+    if let Err(error) = copied {
+        eprintln!("md-preview: no clipboard available: {}", error);
+        if clipboard_only {
+            io::stdout().write_all(text.as_bytes())?;
+        }
+    }
+    Ok(())
+}
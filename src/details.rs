@@ -0,0 +1,94 @@
+//! `<details>` / `<summary>` collapsible sections in raw HTML blocks.
+
+/// A piece of an HTML block that takes part in a collapsible section.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Part {
+    /// `<details>`, with the text of the `<summary>` that follows it in the
+    /// same block, if any
+    Open(Option<String>),
+    Close,
+    /// A line of other text, with any other tags removed
+    Text(String),
+}
+
+/// Whether an HTML block opens or closes a collapsible section.
+pub fn is_details(html: &str) -> bool {
+    let lower = html.to_ascii_lowercase();
+    lower.contains("<details") || lower.contains("</details>")
+}
+
+/// Whether `tag` (starting with `<`) is a `name` tag, allowing attributes.
+fn is_tag(tag: &str, name: &str) -> bool {
+    tag.strip_prefix(name).is_some_and(|rest| rest.starts_with(['>', ' ', '\t', '\n']))
+}
+
+/// `html` without its tags, its whitespace collapsed.
+fn strip_tags(html: &str) -> String {
+    let mut text = String::new();
+    let mut in_tag = false;
+    for ch in html.chars() {
+        match ch {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            _ if !in_tag => text.push(ch),
+            _ => {}
+        }
+    }
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Move the non-blank lines of `text` into `parts`.
+fn flush_text(text: &mut String, parts: &mut Vec<Part>) {
+    for line in std::mem::take(text).lines() {
+        let line = strip_tags(line);
+        if !line.is_empty() {
+            parts.push(Part::Text(line));
+        }
+    }
+}
+
+/// The sections opened and closed by an HTML block, in order, with the text
+/// between them.
+pub fn parts(html: &str) -> Vec<Part> {
+    // ASCII lowercasing keeps byte offsets the same as in `html`
+    let lower = html.to_ascii_lowercase();
+    let mut parts = Vec::new();
+    let mut text = String::new();
+    let mut pos = 0;
+    while let Some(start) = lower[pos..].find('<').map(|at| pos + at) {
+        let Some(end) = lower[start..].find('>').map(|at| start + at + 1) else {
+            break;
+        };
+        let tag = &lower[start..end];
+        if is_tag(tag, "<details") {
+            text.push_str(&html[pos..start]);
+            flush_text(&mut text, &mut parts);
+            parts.push(Part::Open(None));
+        } else if tag == "</details>" {
+            text.push_str(&html[pos..start]);
+            flush_text(&mut text, &mut parts);
+            parts.push(Part::Close);
+        } else if is_tag(tag, "<summary")
+            && let Some(close) = lower[end..].find("</summary>").map(|at| end + at)
+        {
+            let summary = strip_tags(&html[end..close]);
+            match parts.last_mut() {
+                Some(Part::Open(open @ None)) if html[pos..start].trim().is_empty() && text.trim().is_empty() => {
+                    *open = Some(summary)
+                }
+                _ => {
+                    text.push_str(&html[pos..start]);
+                    text.push_str(&summary);
+                }
+            }
+            pos = close + "</summary>".len();
+            continue;
+        } else {
+            text.push_str(&html[pos..end]);
+        }
+        pos = end;
+    }
+    text.push_str(&html[pos..]);
+    flush_text(&mut text, &mut parts);
+    parts
+}
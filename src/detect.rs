@@ -0,0 +1,67 @@
+//! `--detect-language`: guess the language of unlabeled code blocks.
+
+use std::cmp::Reverse;
+
+use pulldown_cmark::{CodeBlockKind, Event, Tag, TagEnd};
+
+/// Telltale snippets for each language. A block is credited once per
+/// snippet it contains.
+const SIGNALS: &[(&str, &[&str])] = &[
+    ("rust", &["fn ", "let ", "println!", "::", "-> ", "impl ", "pub ", "&mut ", "#[derive"]),
+    ("python", &["def ", "import ", "print(", "self.", "elif ", "None", "__init__", "\"\"\""]),
+    ("javascript", &["function ", "const ", "=> ", "console.log", "===", "require(", "document."]),
+    ("go", &["package ", "func ", ":= ", "fmt.", "err != nil"]),
+    ("c", &["#include", "int main", "printf(", "->", "NULL", "void "]),
+    ("bash", &["#!/bin/", "echo ", "sudo ", "export ", "fi\n", "$(", "apt "]),
+    ("html", &["<html", "<div", "</", "<!DOCTYPE", "<p>", "<body"]),
+    ("sql", &["SELECT ", "FROM ", "WHERE ", "INSERT INTO", "CREATE TABLE", "JOIN "]),
+];
+
+/// Signals a guess needs before it's trusted.
+const MIN_SCORE: usize = 2;
+
+/// A best guess at the language of `code`, or `None` when no language is a
+/// clear winner.
+pub fn detect(code: &str) -> Option<&'static str> {
+    let trimmed = code.trim();
+    if (trimmed.starts_with('{') && trimmed.ends_with('}') || trimmed.starts_with('[') && trimmed.ends_with(']'))
+        && trimmed.contains("\":")
+    {
+        return Some("json");
+    }
+
+    let mut scores: Vec<(&str, usize)> = SIGNALS
+        .iter()
+        .map(|(language, signals)| (*language, signals.iter().filter(|signal| code.contains(*signal)).count()))
+        .collect();
+    scores.sort_by_key(|&(_, score)| Reverse(score));
+    match scores.as_slice() {
+        [(language, best), (_, runner_up), ..] if *best >= MIN_SCORE && best > runner_up => Some(language),
+        _ => None,
+    }
+}
+
+/// The guessed language of each code block, in document order; `None` for
+/// labeled blocks and ones without a confident guess.
+pub fn code_languages(events: &[Event]) -> Vec<Option<&'static str>> {
+    let mut languages = Vec::new();
+    let mut unlabeled: Option<String> = None;
+    for event in events {
+        match event {
+            Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(lang))) if !lang.is_empty() => languages.push(None),
+            Event::Start(Tag::CodeBlock(_)) => unlabeled = Some(String::new()),
+            Event::Text(text) => {
+                if let Some(code) = &mut unlabeled {
+                    code.push_str(text);
+                }
+            }
+            Event::End(TagEnd::CodeBlock) => {
+                if let Some(code) = unlabeled.take() {
+                    languages.push(detect(&code));
+                }
+            }
+            _ => {}
+        }
+    }
+    languages
+}
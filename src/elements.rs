@@ -0,0 +1,82 @@
+//! `--list-supported-elements`: which Markdown elements are rendered, and how.
+
+use std::io;
+
+use pulldown_cmark::{Alignment, BlockQuoteKind, Options};
+use termcolor::WriteColor;
+
+use crate::table::{Cell, Row, write_table};
+use crate::Args;
+
+/// How an element is rendered.
+enum Support {
+    /// Always parsed and rendered.
+    Always,
+    /// Rendered when the parser extension is enabled.
+    Extension(Options),
+    /// Not rendered yet.
+    Unsupported,
+}
+
+/// Every element the renderer knows about, as rendered with `args`. Keep this
+/// in step with the match arms in `Renderer` and the extensions in
+/// `InputFormat::options`.
+fn elements(args: &Args) -> Vec<(&'static str, Support, String)> {
+    let (open, done) = args.checkbox_style.markers();
+    let note = args.callout_style.icon(BlockQuoteKind::Note);
+    let math = if args.math_unicode || !args.parser_options().contains(Options::ENABLE_MATH) {
+        Support::Extension(Options::ENABLE_MATH)
+    } else {
+        Support::Unsupported // Parsed, but shown as source
+    };
+    let math_how = if args.math_unicode {
+        "simple formulas in Unicode symbols, the rest as source"
+    } else {
+        "shown as source; simple formulas in Unicode with --math-unicode"
+    };
+    vec![
+        ("Headings", Support::Always, "colored; `#` or setext underlines with --symbol".into()),
+        ("Paragraphs", Support::Always, "plain text".into()),
+        ("Emphasis / strong", Support::Always, "colored; `*` / `**` with --symbol".into()),
+        ("Strikethrough", Support::Extension(Options::ENABLE_STRIKETHROUGH), "colored; `~~` with --symbol".into()),
+        ("Inline code", Support::Always, "colored; backticks with --symbol".into()),
+        ("Code blocks", Support::Always, "colored, `diff` and config blocks highlighted; fences with --symbol".into()),
+        ("Blockquotes", Support::Always, "`>` markers, nested".into()),
+        ("Lists", Support::Always, "ordered and unordered, nested".into()),
+        ("Task lists", Support::Extension(Options::ENABLE_TASKLISTS), format!("`{}` / `{}`; --checkbox-style", done, open)),
+        ("Tables", Support::Extension(Options::ENABLE_TABLES), "aligned columns with borders".into()),
+        ("Links", Support::Always, "text; destinations with --show-urls".into()),
+        ("Images", Support::Always, "alt text; destinations with --show-urls".into()),
+        ("Horizontal rules", Support::Always, "dashes".into()),
+        ("Footnotes", Support::Extension(Options::ENABLE_FOOTNOTES), "`[^name]` references and `[^name]:` definitions; --footnote-superscript".into()),
+        ("Collapsible sections (`<details>`)", Support::Always, "`▸` summary over indented content; --collapse-details".into()),
+        ("Superscript / subscript (`<sup>`, `<sub>`)", Support::Always, "Unicode glyphs; `^(..)` / `_(..)` otherwise".into()),
+        ("Keys (`<kbd>`)", Support::Always, "keycaps; `[Ctrl]` without color".into()),
+        ("Raw HTML", Support::Always, "hidden; comments with --show-comments".into()),
+        (
+            "YAML front matter",
+            Support::Extension(Options::ENABLE_YAML_STYLE_METADATA_BLOCKS),
+            "table with --render-frontmatter-as-table".into(),
+        ),
+        ("Math", math, math_how.into()),
+        ("Definition lists", Support::Extension(Options::ENABLE_DEFINITION_LIST), "term over indented definitions; --glossary".into()),
+        ("Callouts", Support::Extension(Options::ENABLE_GFM), format!("blockquote under a colored `{} Note` header; --callout-style", note)),
+    ]
+}
+
+/// Print the element matrix for rendering with `args`.
+pub fn list<W: WriteColor>(stdout: &mut W, args: &Args) -> io::Result<()> {
+    let options = args.parser_options();
+    let theme = args.theme();
+    let mut rows = vec![Row { header: true, cells: vec![Cell::plain("Element"), Cell::plain("Rendered"), Cell::plain("How")] }];
+    for (name, support, how) in elements(args) {
+        let rendered = match support {
+            Support::Always => "yes",
+            Support::Extension(option) if options.contains(option) => "yes",
+            Support::Extension(_) => "off",
+            Support::Unsupported => "no",
+        };
+        rows.push(Row { header: false, cells: vec![Cell::plain(name), Cell::plain(rendered), Cell::plain(&how)] });
+    }
+    write_table(stdout, &rows, &[Alignment::None; 3], &theme.table_header, &theme.table_border, None, &Cell::default(), None)
+}
@@ -0,0 +1,218 @@
+//! Narrowing the parsed document down to part of it before rendering.
+
+use std::io;
+use std::ops::Range;
+
+use pulldown_cmark::{Event, Tag, TagEnd};
+
+/// Top-level headings as `(event index, heading text)`, in document order.
+fn headings(events: &[Event]) -> Vec<(usize, String)> {
+    let mut headings = Vec::new();
+    let mut depth = 0;
+    let mut current: Option<(usize, String)> = None;
+    for (i, event) in events.iter().enumerate() {
+        match event {
+            Event::Start(Tag::Heading { .. }) if depth == 0 => current = Some((i, String::new())),
+            Event::End(TagEnd::Heading(_)) if depth == 1 => headings.extend(current.take()),
+            Event::Text(text) | Event::Code(text) => {
+                if let Some((_, heading)) = &mut current {
+                    heading.push_str(text);
+                }
+            }
+            _ => {}
+        }
+        match event {
+            Event::Start(_) => depth += 1,
+            Event::End(_) => depth -= 1,
+            _ => {}
+        }
+    }
+    headings
+}
+
+fn not_found(option: &str, heading: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, format!("no heading matches {} `{}`", option, heading))
+}
+
+/// The events from the first top-level heading matching `since` up to, but not
+/// including, the next one matching `until` (or the end of the document).
+/// Headings match their text case-insensitively, ignoring surrounding spaces.
+pub fn heading_range(events: &[Event], since: Option<&str>, until: Option<&str>) -> io::Result<Range<usize>> {
+    let headings = headings(events);
+    let matches = |text: &str, wanted: &str| text.trim().eq_ignore_ascii_case(wanted.trim());
+
+    let mut start = 0;
+    if let Some(since) = since {
+        start = headings
+            .iter()
+            .find(|(_, text)| matches(text, since))
+            .map(|&(i, _)| i)
+            .ok_or_else(|| not_found("--since-heading", since))?;
+    }
+    let mut end = events.len();
+    if let Some(until) = until {
+        end = headings
+            .iter()
+            .find(|&&(i, ref text)| i > start && matches(text, until))
+            .map(|&(i, _)| i)
+            .ok_or_else(|| not_found("--until-heading", until))?;
+    }
+    Ok(start..end)
+}
+
+/// Words that end in a period without ending the sentence.
+const ABBREVIATIONS: &[&str] = &["e.g", "i.e", "etc", "vs", "cf", "mr", "mrs", "ms", "dr", "st", "no", "fig", "approx"];
+
+/// Where the first sentence in `text` ends: just past a `.`, `!` or `?`
+/// followed by whitespace, or at the end of the text when `line_ends` says
+/// nothing follows on the line. Periods after abbreviations and initials
+/// (`e.g.`, `Dr.`, `J.`) don't count.
+fn sentence_end(text: &str, line_ends: bool) -> Option<usize> {
+    for (i, ch) in text.char_indices() {
+        if !matches!(ch, '.' | '!' | '?') {
+            continue;
+        }
+        let end = i + ch.len_utf8();
+        match text[end..].chars().next() {
+            Some(next) if next.is_whitespace() => {}
+            None if line_ends => {}
+            _ => continue,
+        }
+        if ch == '.' {
+            let word = text[..i].rsplit(char::is_whitespace).next().unwrap_or_default();
+            let word = word.trim_start_matches(|c: char| !c.is_alphanumeric());
+            if word.chars().count() == 1 || ABBREVIATIONS.iter().any(|abbr| word.eq_ignore_ascii_case(abbr)) {
+                continue;
+            }
+        }
+        return Some(end);
+    }
+    None
+}
+
+enum Summary {
+    /// Waiting for the next top-level heading.
+    Skipping,
+    /// Copying a heading.
+    Heading,
+    /// Looking for the first paragraph after a heading.
+    WantParagraph,
+    /// Copying that paragraph's first sentence, with the inline tags still open.
+    Paragraph(Vec<TagEnd>, bool),
+}
+
+/// An outline of the document: every top-level heading followed by the first
+/// sentence of the paragraph after it. Code blocks, tables, lists and quotes
+/// are left out. `setext_headings` is narrowed to the headings kept.
+pub fn summary<'a>(events: Vec<Event<'a>>, setext_headings: &mut Vec<bool>) -> Vec<Event<'a>> {
+    let mut kept = Vec::new();
+    let mut kept_setext = Vec::new();
+    let mut heading_index = 0;
+    let mut depth = 0;
+    let mut state = Summary::Skipping;
+    let mut events = events.into_iter().peekable();
+    while let Some(event) = events.next() {
+        let top_level = depth == 0;
+        match &event {
+            Event::Start(_) => depth += 1,
+            Event::End(_) => depth -= 1,
+            _ => {}
+        }
+
+        if let Event::Start(Tag::Heading { .. }) = event {
+            heading_index += 1;
+            if top_level {
+                kept_setext.push(setext_headings.get(heading_index - 1).copied().unwrap_or(false));
+                kept.push(event);
+                state = Summary::Heading;
+                continue;
+            }
+        }
+        match &mut state {
+            Summary::Skipping => {}
+            Summary::Heading => {
+                kept.push(event);
+                if depth == 0 {
+                    state = Summary::WantParagraph; // That was the heading's end
+                }
+            }
+            Summary::WantParagraph => {
+                if top_level && matches!(event, Event::Start(Tag::Paragraph)) {
+                    kept.push(event);
+                    state = Summary::Paragraph(Vec::new(), false);
+                }
+            }
+            Summary::Paragraph(open, done) => {
+                if depth == 0 {
+                    kept.push(event); // The paragraph's end
+                    state = Summary::Skipping;
+                    continue;
+                }
+                if *done {
+                    continue;
+                }
+                if let Event::Text(text) = &event {
+                    let line_ends = matches!(
+                        events.peek(),
+                        Some(Event::SoftBreak | Event::HardBreak | Event::End(TagEnd::Paragraph))
+                    );
+                    match sentence_end(text, line_ends) {
+                        Some(end) => {
+                            kept.push(Event::Text(text[..end].to_string().into()));
+                            kept.extend(open.drain(..).rev().map(Event::End));
+                            *done = true;
+                        }
+                        None => kept.push(event),
+                    }
+                } else {
+                    match &event {
+                        Event::Start(tag) => open.push(tag.to_end()),
+                        Event::End(_) => {
+                            open.pop();
+                        }
+                        _ => {}
+                    }
+                    kept.push(event);
+                }
+            }
+        }
+    }
+    *setext_headings = kept_setext;
+    kept
+}
+
+/// Only the top-level blocks for which `keep` returns true, nested content
+/// included. `setext_headings` is narrowed to the headings kept.
+pub fn blocks<'a>(events: Vec<Event<'a>>, setext_headings: &mut Vec<bool>, keep: impl Fn(&Tag) -> bool) -> Vec<Event<'a>> {
+    let mut kept = Vec::new();
+    let mut kept_setext = Vec::new();
+    let mut heading_index = 0;
+    let mut depth = 0;
+    let mut keeping = false;
+    for event in events {
+        if depth == 0
+            && let Event::Start(tag) = &event
+        {
+            keeping = keep(tag);
+        }
+        match &event {
+            Event::Start(Tag::Heading { .. }) => {
+                if keeping {
+                    kept_setext.push(setext_headings.get(heading_index).copied().unwrap_or(false));
+                }
+                heading_index += 1;
+                depth += 1;
+            }
+            Event::Start(_) => depth += 1,
+            Event::End(_) => depth -= 1,
+            // Rules are the only top-level content outside a block
+            _ if depth == 0 => keeping = false,
+            _ => {}
+        }
+        if keeping {
+            kept.push(event);
+        }
+    }
+    *setext_headings = kept_setext;
+    kept
+}
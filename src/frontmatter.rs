@@ -0,0 +1,59 @@
+//! `--render-frontmatter-as-table`: YAML front matter shown as key/value rows.
+
+use serde_yaml::Value;
+
+use crate::table::{Cell, Row};
+
+/// A scalar as it would be written in YAML, without quotes.
+fn scalar(value: &Value) -> Option<String> {
+    match value {
+        Value::Null => Some(String::new()),
+        Value::Bool(b) => Some(b.to_string()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::String(s) => Some(s.clone()),
+        Value::Tagged(tagged) => scalar(&tagged.value),
+        Value::Sequence(_) | Value::Mapping(_) => None,
+    }
+}
+
+/// Collect `key: value` pairs, flattening nested mappings to dotted keys.
+/// Lists of scalars become comma-separated values; other lists are indexed.
+fn flatten(key: String, value: &Value, pairs: &mut Vec<(String, String)>) {
+    if let Some(text) = scalar(value) {
+        pairs.push((key, text));
+        return;
+    }
+    let join = |inner: &str| if key.is_empty() { inner.to_string() } else { format!("{}.{}", key, inner) };
+    match value {
+        Value::Sequence(items) => match items.iter().map(scalar).collect::<Option<Vec<_>>>() {
+            Some(texts) => pairs.push((key, texts.join(", "))),
+            None => {
+                for (i, item) in items.iter().enumerate() {
+                    flatten(join(&i.to_string()), item, pairs);
+                }
+            }
+        },
+        Value::Mapping(map) => {
+            for (inner, item) in map {
+                let inner = scalar(inner).unwrap_or_default();
+                flatten(join(&inner), item, pairs);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// The table rows for a YAML front matter block: a `Key | Value` header and
+/// one row per (flattened) key. `None` if the YAML isn't a mapping.
+pub fn rows(yaml: &str) -> Option<Vec<Row>> {
+    let value: Value = serde_yaml::from_str(yaml).ok()?;
+    if !value.is_mapping() {
+        return None;
+    }
+    let mut pairs = Vec::new();
+    flatten(String::new(), &value, &mut pairs);
+
+    let mut rows = vec![Row { header: true, cells: vec![Cell::plain("Key"), Cell::plain("Value")] }];
+    rows.extend(pairs.iter().map(|(key, value)| Row { header: false, cells: vec![Cell::plain(key), Cell::plain(value)] }));
+    Some(rows)
+}
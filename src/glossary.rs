@@ -0,0 +1,50 @@
+//! `--glossary`: the terms defined in definition lists, picked out wherever
+//! the prose uses them.
+
+use pulldown_cmark::{Event, Tag, TagEnd};
+
+/// The text of every definition list title, longest first so that a longer
+/// term wins over one it contains, without duplicates.
+pub fn terms(events: &[Event]) -> Vec<String> {
+    let mut terms: Vec<String> = Vec::new();
+    let mut title: Option<String> = None;
+    for event in events {
+        match event {
+            Event::Start(Tag::DefinitionListTitle) => title = Some(String::new()),
+            Event::End(TagEnd::DefinitionListTitle) => {
+                if let Some(term) = title.take().map(|term| term.trim().to_string())
+                    && !term.is_empty()
+                    && !terms.iter().any(|known| known.eq_ignore_ascii_case(&term))
+                {
+                    terms.push(term);
+                }
+            }
+            Event::Text(text) | Event::Code(text) => {
+                if let Some(title) = &mut title {
+                    title.push_str(text);
+                }
+            }
+            _ => {}
+        }
+    }
+    terms.sort_by_key(|term| std::cmp::Reverse(term.len()));
+    terms
+}
+
+/// Where the earliest of `terms` is used as a whole word in `text`, ignoring
+/// ASCII case, and how long the match is.
+pub fn next_use(text: &str, terms: &[String]) -> Option<(usize, usize)> {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    let lower = text.to_ascii_lowercase();
+    terms
+        .iter()
+        .filter_map(|term| {
+            let term = term.to_ascii_lowercase();
+            lower
+                .match_indices(term.as_str())
+                .find(|&(at, _)| !text[..at].ends_with(is_word) && !text[at + term.len()..].starts_with(is_word))
+                .map(|(at, _)| (at, term.len()))
+        })
+        // Earliest first; at the same place, the longest term
+        .min_by_key(|&(at, len)| (at, std::cmp::Reverse(len)))
+}
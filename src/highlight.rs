@@ -0,0 +1,320 @@
+//! Line-based highlighting for code blocks in a few simple languages: `diff`,
+//! the config formats `toml`, `yaml` and `ini`, and shell sessions, plus
+//! comments alone in languages picked out by their comment syntax.
+
+/// What a piece of a highlighted line is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    /// Anything without a color of its own
+    Plain,
+    Added,
+    Removed,
+    Hunk,
+    Key,
+    Section,
+    Comment,
+    Prompt,
+    Command,
+    /// Program output in a shell session
+    Output,
+}
+
+/// The languages highlighted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Language {
+    Diff,
+    Toml,
+    Yaml,
+    Ini,
+    Shell,
+    /// Any other language, with only its comments picked out
+    Comments(CommentSyntax),
+}
+
+/// How a language writes comments.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CommentSyntax {
+    /// `// line` and `/* block */`, as in C and its descendants
+    Slash,
+    /// `# line`, as in scripting languages
+    Hash,
+    /// `-- line`, as in SQL, Lua and Haskell
+    DashDash,
+}
+
+impl CommentSyntax {
+    /// The comment syntax of a code block's language, if it is known.
+    pub fn from_info(info: &str) -> Option<Self> {
+        match info.split_whitespace().next()? {
+            "c" | "h" | "cpp" | "c++" | "cc" | "cs" | "csharp" | "java" | "kotlin" | "scala" | "swift" | "go" | "rust"
+            | "rs" | "js" | "javascript" | "jsx" | "ts" | "typescript" | "tsx" | "php" | "dart" | "zig" | "css" | "scss"
+            | "json5" | "jsonc" => Some(CommentSyntax::Slash),
+            "python" | "py" | "ruby" | "rb" | "perl" | "pl" | "r" | "bash" | "sh" | "shell" | "zsh" | "fish" | "dockerfile"
+            | "makefile" | "make" | "cmake" | "elixir" | "ex" | "nim" | "powershell" | "ps1" | "tcl" | "awk" => {
+                Some(CommentSyntax::Hash)
+            }
+            "sql" | "lua" | "haskell" | "hs" | "elm" | "ada" => Some(CommentSyntax::DashDash),
+            _ => None,
+        }
+    }
+
+    /// The quotes strings may use, inside which nothing is a comment. Single
+    /// quotes are left out where they also mark lifetimes and characters.
+    fn quotes(self) -> &'static [char] {
+        match self {
+            CommentSyntax::Slash => &['"', '`'],
+            CommentSyntax::Hash | CommentSyntax::DashDash => &['"', '\''],
+        }
+    }
+}
+
+impl Language {
+    /// The language for a code block's info string, if it is highlighted.
+    pub fn from_info(info: &str) -> Option<Self> {
+        match info.split_whitespace().next()? {
+            "diff" | "patch" => Some(Language::Diff),
+            "toml" => Some(Language::Toml),
+            "yaml" | "yml" => Some(Language::Yaml),
+            "ini" | "cfg" | "conf" => Some(Language::Ini),
+            "bash" | "sh" | "shell" | "console" | "shell-session" => Some(Language::Shell),
+            _ => None,
+        }
+    }
+
+}
+
+/// Split a `{1,3-5}` line annotation off the end of a code block's info
+/// string, returning the rest and the 1-based inclusive line ranges. A
+/// malformed annotation marks no lines, but is still split off.
+pub fn marked_lines(info: &str) -> (&str, Vec<(usize, usize)>) {
+    let trimmed = info.trim_end();
+    let Some(open) = trimmed.rfind('{').filter(|_| trimmed.ends_with('}')) else {
+        return (info, Vec::new());
+    };
+    let ranges = trimmed[open + 1..trimmed.len() - 1]
+        .split(',')
+        .map(|range| {
+            let (start, end) = range.split_once('-').unwrap_or((range, range));
+            let (start, end) = (start.trim().parse().ok()?, end.trim().parse().ok()?);
+            (start <= end).then_some((start, end))
+        })
+        .collect::<Option<Vec<_>>>()
+        .unwrap_or_default();
+    (trimmed[..open].trim_end(), ranges)
+}
+
+/// Highlights a code block line by line, remembering what it needs to from
+/// earlier lines.
+pub struct Highlighter {
+    language: Language,
+    continued: bool, // The last shell command ended in `\`
+    prompted: bool, // A shell prompt was seen; lines before the first keep the code color
+    in_block_comment: bool, // Inside a `/* */` comment begun on an earlier line
+}
+
+impl Highlighter {
+    pub fn new(language: Language) -> Self {
+        Highlighter { language, continued: false, prompted: false, in_block_comment: false }
+    }
+
+    /// Split `line` (including any newline) into highlighted pieces.
+    pub fn spans<'a>(&mut self, line: &'a str) -> Vec<(Kind, &'a str)> {
+        match self.language {
+            Language::Diff => vec![(diff_kind(line), line)],
+            Language::Toml => key_value_spans(line, "#"),
+            Language::Ini => key_value_spans(line, ";#"),
+            Language::Yaml => yaml_spans(line),
+            Language::Shell => self.shell_spans(line),
+            Language::Comments(syntax) => self.comment_spans(line, syntax),
+        }
+    }
+
+    /// Comments, both whole-line and trailing, and the code around them.
+    fn comment_spans<'a>(&mut self, line: &'a str, syntax: CommentSyntax) -> Vec<(Kind, &'a str)> {
+        let mut spans = Vec::new();
+        let mut rest = line;
+        loop {
+            if self.in_block_comment {
+                match rest.find("*/") {
+                    Some(end) => {
+                        spans.push((Kind::Comment, &rest[..end + 2]));
+                        rest = &rest[end + 2..];
+                        self.in_block_comment = false;
+                    }
+                    None => {
+                        to_line_end(rest, &mut spans);
+                        return spans;
+                    }
+                }
+            }
+            match code_comment_start(rest, syntax) {
+                Some((start, block)) => {
+                    spans.push((Kind::Plain, &rest[..start]));
+                    if block {
+                        spans.push((Kind::Comment, &rest[start..start + 2]));
+                        rest = &rest[start + 2..];
+                        self.in_block_comment = true;
+                    } else {
+                        to_line_end(&rest[start..], &mut spans);
+                        return spans;
+                    }
+                }
+                None => {
+                    spans.push((Kind::Plain, rest));
+                    return spans;
+                }
+            }
+        }
+    }
+
+    /// A `$ ` or `# ` prompt followed by its command, a continuation of the
+    /// previous command, or output. Blocks are only treated as sessions from
+    /// their first prompt on, so plain scripts stay as they are.
+    fn shell_spans<'a>(&mut self, line: &'a str) -> Vec<(Kind, &'a str)> {
+        let trimmed = line.trim_start();
+        let spans = if self.continued {
+            vec![(Kind::Command, line)]
+        } else if trimmed.starts_with("$ ") || trimmed.starts_with("# ") {
+            let (indent, prompt) = line.split_at(line.len() - trimmed.len());
+            let (prompt, command) = prompt.split_at(2);
+            self.prompted = true;
+            vec![(Kind::Output, indent), (Kind::Prompt, prompt), (Kind::Command, command)]
+        } else if self.prompted {
+            vec![(Kind::Output, line)]
+        } else {
+            vec![(Kind::Plain, line)]
+        };
+        self.continued = spans.last().is_some_and(|&(kind, _)| kind == Kind::Command) && line.trim_end().ends_with('\\');
+        spans
+    }
+}
+
+fn diff_kind(line: &str) -> Kind {
+    if line.starts_with("+++") || line.starts_with("---") {
+        Kind::Plain
+    } else if line.starts_with('+') {
+        Kind::Added
+    } else if line.starts_with('-') {
+        Kind::Removed
+    } else if line.starts_with("@@") {
+        Kind::Hunk
+    } else {
+        Kind::Plain
+    }
+}
+
+/// Where a trailing comment starts in `text`: a comment character at the
+/// start or after whitespace, outside quotes.
+fn comment_start(text: &str, markers: &str) -> Option<usize> {
+    let mut quote = None;
+    let mut previous = ' ';
+    for (i, ch) in text.char_indices() {
+        match quote {
+            Some(open) if ch == open => quote = None,
+            Some(_) => {}
+            None if ch == '"' || ch == '\'' => quote = Some(ch),
+            None if markers.contains(ch) && previous.is_whitespace() => return Some(i),
+            None => {}
+        }
+        previous = ch;
+    }
+    None
+}
+
+/// A comment running to the end of the line, keeping the newline plain.
+fn to_line_end<'a>(comment: &'a str, spans: &mut Vec<(Kind, &'a str)>) {
+    let (comment, end) = comment.split_at(comment.trim_end_matches('\n').len());
+    spans.push((Kind::Comment, comment));
+    spans.push((Kind::Plain, end));
+}
+
+/// Where a comment starts in a line of code, outside strings and at the start
+/// of the line or after whitespace, and whether it is a `/*` block comment.
+fn code_comment_start(code: &str, syntax: CommentSyntax) -> Option<(usize, bool)> {
+    let mut quote = None;
+    let mut previous = ' ';
+    for (i, ch) in code.char_indices() {
+        match quote {
+            Some(open) if ch == open && previous != '\\' => quote = None,
+            Some(_) => {}
+            None if syntax.quotes().contains(&ch) => quote = Some(ch),
+            None if previous.is_whitespace() => {
+                let rest = &code[i..];
+                match syntax {
+                    CommentSyntax::Slash if rest.starts_with("//") => return Some((i, false)),
+                    CommentSyntax::Slash if rest.starts_with("/*") => return Some((i, true)),
+                    CommentSyntax::Hash if rest.starts_with('#') => return Some((i, false)),
+                    CommentSyntax::DashDash if rest.starts_with("--") => return Some((i, false)),
+                    _ => {}
+                }
+            }
+            None => {}
+        }
+        // An escaped backslash doesn't escape what follows it
+        previous = if previous == '\\' && ch == '\\' { ' ' } else { ch };
+    }
+    None
+}
+
+/// `value` followed by its trailing comment, if any.
+fn value_spans<'a>(value: &'a str, markers: &str, spans: &mut Vec<(Kind, &'a str)>) {
+    match comment_start(value, markers) {
+        Some(start) => {
+            let (value, comment) = value.split_at(start);
+            spans.push((Kind::Plain, value));
+            let newline = comment.len() - comment.trim_end_matches('\n').len();
+            let (comment, end) = comment.split_at(comment.len() - newline);
+            spans.push((Kind::Comment, comment));
+            spans.push((Kind::Plain, end));
+        }
+        None => spans.push((Kind::Plain, value)),
+    }
+}
+
+/// TOML and INI: `[section]` headers, `key = value` pairs and comments.
+fn key_value_spans<'a>(line: &'a str, markers: &str) -> Vec<(Kind, &'a str)> {
+    let trimmed = line.trim_start();
+    if trimmed.starts_with(|c| markers.contains(c)) {
+        return vec![(Kind::Comment, line)];
+    }
+    if trimmed.starts_with('[') {
+        return vec![(Kind::Section, line)];
+    }
+    let mut spans = Vec::new();
+    match line.find('=') {
+        Some(at) => {
+            spans.push((Kind::Key, &line[..at]));
+            value_spans(&line[at..], markers, &mut spans);
+        }
+        None => value_spans(line, markers, &mut spans),
+    }
+    spans
+}
+
+/// YAML: `key:` mappings (also inside `- ` list items), `---` document
+/// markers and comments.
+fn yaml_spans(line: &str) -> Vec<(Kind, &str)> {
+    let trimmed = line.trim_start();
+    if trimmed.starts_with('#') {
+        return vec![(Kind::Comment, line)];
+    }
+    if trimmed.starts_with("---") || trimmed.starts_with("...") {
+        return vec![(Kind::Section, line)];
+    }
+    let mut spans = Vec::new();
+    // The key starts after the indentation and any `- ` list markers
+    let key_start = line.len() - trimmed.trim_start_matches(['-', ' ']).len();
+    let rest = &line[key_start..];
+    let before_comment = &rest[..comment_start(rest, "#").unwrap_or(rest.len())];
+    let key_end = before_comment.find(": ").or_else(|| before_comment.trim_end().strip_suffix(':').map(str::len));
+    match key_end {
+        // Flow collections like `{a: 1}` are values, not keys
+        Some(key_end) if !rest.starts_with(['{', '[']) => {
+            spans.push((Kind::Plain, &line[..key_start]));
+            spans.push((Kind::Key, &rest[..key_end]));
+            value_spans(&rest[key_end..], "#", &mut spans);
+        }
+        _ => value_spans(line, "#", &mut spans),
+    }
+    spans
+}
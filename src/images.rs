@@ -0,0 +1,94 @@
+//! `--inline-images`: local images drawn in the terminal with the kitty or
+//! iTerm2 graphics protocol.
+
+use std::env;
+use std::io::{self, Cursor};
+use std::path::Path;
+
+use image::{ImageFormat, ImageReader};
+
+/// Columns an image is assumed to take per this many pixels of width.
+const PIXELS_PER_COLUMN: u32 = 8;
+
+/// Base64 payload bytes per kitty escape sequence, as the protocol requires.
+const KITTY_CHUNK: usize = 4096;
+
+/// A terminal graphics protocol.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Protocol {
+    Kitty,
+    /// iTerm2's inline images, also understood by WezTerm
+    Iterm,
+}
+
+impl Protocol {
+    /// The protocol the terminal speaks, going by the variables it sets.
+    pub fn detect() -> Option<Self> {
+        if env::var_os("KITTY_WINDOW_ID").is_some() || env::var("TERM").is_ok_and(|term| term == "xterm-kitty") {
+            return Some(Protocol::Kitty);
+        }
+        match env::var("TERM_PROGRAM").as_deref() {
+            Ok("iTerm.app" | "WezTerm") => Some(Protocol::Iterm),
+            _ => None,
+        }
+    }
+
+    /// The escape sequence drawing the image at `path`, at most `width`
+    /// columns wide. Fails if the file is missing or isn't a PNG or JPEG.
+    pub fn draw(self, path: &Path, width: usize) -> io::Result<String> {
+        let bytes = std::fs::read(path)?;
+        let decoded = ImageReader::new(Cursor::new(&bytes))
+            .with_guessed_format()?
+            .decode()
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        let columns = decoded.width().div_ceil(PIXELS_PER_COLUMN).clamp(1, width.max(1) as u32);
+        match self {
+            Protocol::Kitty => {
+                // Kitty takes PNG data whatever the file's format was
+                let mut png = Vec::new();
+                decoded
+                    .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+                    .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+                let payload = base64(&png);
+                let chunks: Vec<&str> = payload
+                    .as_bytes()
+                    .chunks(KITTY_CHUNK)
+                    .map(|chunk| std::str::from_utf8(chunk).unwrap_or_default())
+                    .collect();
+                let mut escape = String::new();
+                for (i, chunk) in chunks.iter().enumerate() {
+                    let more = u8::from(i + 1 < chunks.len());
+                    if i == 0 {
+                        escape.push_str(&format!("\x1b_Ga=T,f=100,c={},q=2,m={};{}\x1b\\", columns, more, chunk));
+                    } else {
+                        escape.push_str(&format!("\x1b_Gm={};{}\x1b\\", more, chunk));
+                    }
+                }
+                Ok(escape)
+            }
+            Protocol::Iterm => Ok(format!(
+                "\x1b]1337;File=inline=1;size={};width={};preserveAspectRatio=1:{}\x07",
+                bytes.len(),
+                columns,
+                base64(&bytes)
+            )),
+        }
+    }
+}
+
+/// Standard, padded base64.
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for group in bytes.chunks(3) {
+        let n = group.iter().enumerate().fold(0u32, |n, (i, &byte)| n | (byte as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= group.len() {
+                encoded.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
@@ -0,0 +1,45 @@
+//! `--pretty-json`: re-indent the contents of ```` ```json ```` code blocks.
+
+use pulldown_cmark::{CodeBlockKind, CowStr, Event, Tag, TagEnd};
+
+/// `code` re-serialized with indentation, or `None` if it isn't valid JSON.
+fn pretty(code: &str) -> Option<String> {
+    let value: serde_json::Value = serde_json::from_str(code).ok()?;
+    let mut pretty = serde_json::to_string_pretty(&value).ok()?;
+    pretty.push('\n');
+    Some(pretty)
+}
+
+/// The events with the text of every `json` code block pretty-printed. Blocks
+/// that don't parse as JSON are left as they are.
+pub fn prettify(events: Vec<Event>) -> Vec<Event> {
+    let mut prettified = Vec::with_capacity(events.len());
+    let mut block: Option<Vec<Event>> = None; // Text events of the open json block
+    for event in events {
+        match event {
+            Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(ref lang)))
+                if lang.split_whitespace().next() == Some("json") =>
+            {
+                prettified.push(event);
+                block = Some(Vec::new());
+            }
+            Event::End(TagEnd::CodeBlock) if block.is_some() => {
+                let texts = block.take().unwrap_or_default();
+                let code: String = texts.iter().filter_map(|text| match text {
+                    Event::Text(text) => Some(text.as_ref()),
+                    _ => None,
+                }).collect();
+                match pretty(&code) {
+                    Some(pretty) => prettified.push(Event::Text(CowStr::from(pretty))),
+                    None => prettified.extend(texts),
+                }
+                prettified.push(event);
+            }
+            event => match &mut block {
+                Some(texts) => texts.push(event),
+                None => prettified.push(event),
+            },
+        }
+    }
+    prettified
+}
@@ -1,430 +1,1218 @@
-use std::{fs, io::{self, Write}};
-use clap::Parser;
-use pulldown_cmark::{Parser as MarkdownParser, Event, Tag, CodeBlockKind, TagEnd, Options, Alignment};
-use termcolor::{Color, ColorChoice, ColorSpec, StandardStream, WriteColor};
+use std::{cell::Cell, fs, io::{self, Write}, path::{Path, PathBuf}, rc::Rc, time::{Duration, Instant}};
+use clap::{Parser, ValueEnum};
+use pulldown_cmark::{html, BlockQuoteKind, Parser as MarkdownParser, Event, HeadingLevel, Tag, TagEnd, Options};
+use termcolor::{Ansi, ColorChoice, NoColor, StandardStream, WriteColor};
+
+mod badges;
+#[cfg(feature = "clipboard")]
+mod clipboard;
+mod elements;
+mod detect;
+mod details;
+mod filter;
+mod frontmatter;
+mod glossary;
+mod highlight;
+#[cfg(feature = "inline-images")]
+mod images;
+mod json;
+mod math;
+mod mermaid;
+mod output;
+#[cfg(feature = "interactive")]
+mod pager;
+mod progress;
+mod regions;
+mod render;
+mod script;
+mod source;
+#[cfg(feature = "svg")]
+mod svg;
+mod table;
+mod term;
+mod theme;
+mod validate;
+mod watch;
+
+use output::{LimitBytes, PreviewSafe, ResetOnDrop, ShowEscapes, ShowTabs};
+use regions::Region;
+use render::{Renderer, WrapIndent};
+use source::{InputEncoding, LineRange};
+use theme::{ColorDepth, Theme};
 
 // 1. Argument Parsing with Clap
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
     /// Path to the Markdown file
-    #[arg(value_name = "FILE")]
-    file: String,
+    #[arg(value_name = "FILE", required_unless_present_any = ["theme_preview", "list_supported_elements"])]
+    file: Option<String>,
+
+    /// Render a built-in sample of every styled element instead of FILE
+    #[arg(long, conflicts_with = "watch")]
+    theme_preview: bool,
+
+    /// Print which Markdown elements are rendered, and how, then exit
+    #[arg(long)]
+    list_supported_elements: bool,
 
     /// Turn markdown symbol rendering on
     #[arg(short, long)]
     symbol: bool,
 
+    /// In symbol mode, leave out code block fences and language labels
+    #[arg(long, requires = "symbol")]
+    no_symbol_fences: bool,
+
+    /// In symbol mode, underline setext headings with this character, or with
+    /// `H1,H2` characters per level (e.g. `═,─`) [default: =,-]
+    #[arg(long, value_name = "CHARS", value_delimiter = ',', requires = "symbol")]
+    heading_underline_char: Vec<char>,
+
+    /// Prefix each heading with a glyph for its level (▍ for H1, ▎ for H2, ...)
+    #[arg(long)]
+    heading_icons: bool,
+
+    /// Never color the output, even on a terminal
+    #[arg(long)]
+    no_color: bool,
+
+    /// Colors to use: the 16 basic ones, the 256-color palette or 24-bit
+    /// truecolor [default: truecolor if $COLORTERM says so, otherwise 256]
+    #[arg(long, value_name = "DEPTH")]
+    color_depth: Option<ColorDepth>,
+
+    /// Show `{#id .class}` heading attributes, dimmed, after the heading
+    #[arg(long)]
+    show_heading_attributes: bool,
+
+    /// Give each heading level its own color instead of a single blue
+    #[arg(long)]
+    heading_color_by_level: bool,
+
+    /// Color blockquote markers and text by nesting depth, like reply levels in email
+    #[arg(long)]
+    nested_quote_colors: bool,
+
     /// Increment left side space to center
     #[arg(short, long, default_value_t = 0)]
     center: usize,
+
+    /// Center headings, rules and short paragraphs in the output width
+    #[arg(long)]
+    true_center: bool,
+
+    /// Draw this glyph (e.g. '│') at each indent level instead of a tab
+    #[arg(long, value_name = "CHAR")]
+    indent_char: Option<char>,
+
+    /// Indent each nesting level by two spaces instead of a tab
+    #[arg(long, conflicts_with = "indent_char")]
+    two_space_indent: bool,
+
+    /// Show each tab written to the output as a dimmed `→   ` marker
+    #[arg(long)]
+    show_tabs: bool,
+
+    /// Print read/parse/render timings to stderr
+    #[arg(long, hide = true)]
+    time: bool,
+
+    /// Don't add a final newline when the output doesn't end with one
+    #[arg(long)]
+    no_trailing_newline: bool,
+
+    /// Markdown flavor the source is written in; the --enable-* flags
+    /// override the extensions it selects
+    #[arg(long, value_enum, default_value_t = InputFormat::Gfm)]
+    input_format: InputFormat,
+
+    /// Parse `[^name]` footnotes [default: on with --input-format gfm]
+    #[arg(long, value_name = "BOOL", num_args = 0..=1, require_equals = true, default_missing_value = "true", help_heading = "Parser extensions")]
+    enable_footnotes: Option<bool>,
+
+    /// Parse `- [ ]` task list items [default: on with --input-format gfm]
+    #[arg(long, value_name = "BOOL", num_args = 0..=1, require_equals = true, default_missing_value = "true", help_heading = "Parser extensions")]
+    enable_tasklists: Option<bool>,
+
+    /// Turn straight quotes, `--` and `...` into typographic ones [default: off]
+    #[arg(long, value_name = "BOOL", num_args = 0..=1, require_equals = true, default_missing_value = "true", help_heading = "Parser extensions")]
+    enable_smart_punctuation: Option<bool>,
+
+    /// Parse `{#id .class}` attributes after headings [default: off]
+    #[arg(long, value_name = "BOOL", num_args = 0..=1, require_equals = true, default_missing_value = "true", help_heading = "Parser extensions")]
+    enable_heading_attributes: Option<bool>,
+
+    /// Parse `$inline$` and `$$display$$` math [default: off]
+    #[arg(long, value_name = "BOOL", num_args = 0..=1, require_equals = true, default_missing_value = "true", help_heading = "Parser extensions")]
+    enable_math: Option<bool>,
+
+    /// Write simple math with Unicode symbols (`\alpha` as α, `x^2` as x²),
+    /// leaving LaTeX it doesn't know as written; turns on --enable-math
+    #[arg(long)]
+    math_unicode: bool,
+
+    /// Parse YAML front matter, hiding it unless --render-frontmatter-as-table
+    /// is given [default: off]
+    #[arg(long, value_name = "BOOL", num_args = 0..=1, require_equals = true, default_missing_value = "true", help_heading = "Parser extensions")]
+    enable_metadata: Option<bool>,
+
+    /// Print the parser events instead of rendering
+    #[arg(long, hide = true)]
+    dump_events: bool,
+
+    /// With --dump-events, prefix each event with its source line and byte range
+    #[arg(long, hide = true, requires = "dump_events")]
+    source_ranges: bool,
+
+    /// Show the escape sequences in the output as text (`\e[1m`) instead of
+    /// sending them to the terminal, colors always on
+    #[arg(long, hide = true, conflicts_with_all = ["no_color", "output", "to"])]
+    show_escapes: bool,
+
+    /// Collapse blockquote markers nested deeper than N into a single `>(depth)` marker
+    #[arg(long, value_name = "N")]
+    max_blockquote_depth: Option<usize>,
+
+    /// Mark quoted lines with a vertical bar instead of `> `: `▎`, or CHAR
+    /// as in `--quote-bar=│`
+    #[arg(long, value_name = "CHAR", num_args = 0..=1, require_equals = true, default_missing_value = "▎")]
+    quote_bar: Option<char>,
+
+    /// Leave tables unformatted, showing their Markdown source as text
+    #[arg(long, help_heading = "Disable elements")]
+    no_tables: bool,
+
+    /// Write code blocks and inline code without the code color
+    #[arg(long, help_heading = "Disable elements")]
+    no_code_color: bool,
+
+    /// Leave out the `> ` markers in front of quoted lines
+    #[arg(long, help_heading = "Disable elements")]
+    no_blockquote_indent: bool,
+
+    /// Print the number of words in the prose (not code) and exit
+    #[arg(long)]
+    word_count_only: bool,
+
+    /// Print only the tables, as CSV, separated by blank lines
+    #[arg(long, conflicts_with_all = ["no_tables", "label_regions", "to"])]
+    tables_as_csv: bool,
+
+    /// Print the rendered output's size as WIDTHxHEIGHT instead of the output
+    #[arg(long, conflicts_with_all = ["output", "watch"])]
+    measure: bool,
+
+    /// Fail if the document uses anything that can't be rendered faithfully,
+    /// such as raw HTML or math, listing what was found
+    #[arg(long)]
+    strict: bool,
+
+    /// Report duplicate heading slugs, skipped heading levels, empty links,
+    /// links to missing headings or files, ragged tables and unclosed emphasis
+    /// on stderr instead of rendering, failing if there are any
+    #[arg(long)]
+    validate: bool,
+
+    /// With --validate, render the document as well
+    #[arg(long, requires = "validate")]
+    render: bool,
+
+    /// Resolve relative image and link paths against DIR instead of the
+    /// document's own directory
+    #[arg(long, value_name = "DIR")]
+    base_path: Option<String>,
+
+    /// Show YAML front matter as a key/value table above the document
+    #[arg(long)]
+    render_frontmatter_as_table: bool,
+
+    /// Mark where the front matter, body and footer (whatever follows the
+    /// last top-level rule, if no heading does) begin with labelled rules
+    #[arg(long)]
+    label_regions: bool,
+
+    /// Right-align a final `— Author` line in a blockquote as its attribution
+    #[arg(long)]
+    quote_attribution: bool,
+
+    /// Show numeric footnote references as superscripts (¹²³) instead of `[^1]`
+    #[arg(long)]
+    footnote_superscript: bool,
+
+    /// Join source-wrapped lines with a space instead of keeping their line breaks
+    #[arg(long)]
+    soft_break_as_space: bool,
+
+    /// Glyphs for task list checkboxes
+    #[arg(long, value_enum, default_value_t = CheckboxStyle::Ascii)]
+    checkbox_style: CheckboxStyle,
+
+    /// Glyphs for the icons of `> [!NOTE]` style callouts
+    #[arg(long, value_enum, default_value_t = CalloutStyle::Ascii)]
+    callout_style: CalloutStyle,
+
+    /// Numbering for ordered list items
+    #[arg(long, value_enum, default_value_t = OrderedStyle::Decimal)]
+    ordered_style: OrderedStyle,
+
+    /// Show each fenced code block's language, dimmed, at the right end of
+    /// its first line
+    #[arg(long)]
+    show_code_lang: bool,
+
+    /// Number nested ordered lists in outline style, each level taking the
+    /// next of upper-roman, upper-alpha, decimal, lower-alpha and lower-roman
+    /// after its parent's
+    #[arg(long)]
+    nested_ordered_styles: bool,
+
+    /// How to style the text of checked task list items
+    #[arg(long, value_enum, default_value_t = CompletedTasks::Plain)]
+    completed_tasks: CompletedTasks,
+
+    /// Show a bar of how many tasks are done above each task list
+    #[arg(long)]
+    progress: bool,
+
+    /// Show task lists as their progress bar alone, without the items
+    #[arg(long)]
+    progress_only: bool,
+
+    /// Output format
+    #[arg(long, value_enum, default_value_t = OutputFormat::Terminal)]
+    to: OutputFormat,
+
+    /// Write the output to FILE instead of stdout
+    #[arg(short, long, value_name = "FILE")]
+    output: Option<String>,
+
+    /// Render HTML comments dimmed instead of dropping them
+    #[arg(long)]
+    show_comments: bool,
+
+    /// Print notices about the document to stderr
+    #[arg(short, long)]
+    verbose: bool,
+
+    /// Expand tabs inside code blocks to stops every N columns (0 keeps the tabs)
+    #[arg(long, value_name = "N", default_value_t = 4)]
+    tab_size_for_code: usize,
+
+    /// Expand tabs inside table cells to stops every N columns
+    #[arg(long, value_name = "N", default_value_t = 4, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    tab_size_for_tables: usize,
+
+    /// Spell out each `*[ABBR]: expansion` abbreviation, dimmed, where it is
+    /// first used (definitions are never shown)
+    #[arg(long)]
+    abbr: bool,
+
+    /// Parse definition lists and pick out their terms wherever the rest of
+    /// the document uses them, as whole words in any case
+    #[arg(long)]
+    glossary: bool,
+
+    /// Color `#heading` links by whether the heading exists in the document
+    #[arg(long)]
+    check_anchors: bool,
+
+    /// Start with a table of contents, its entries linked to their `#slug`
+    /// sections in terminals that support hyperlinks
+    #[arg(long)]
+    render_toc_with_links: bool,
+
+    /// Show link and image destinations (and titles) after their text
+    #[arg(short = 'u', long)]
+    show_urls: bool,
+
+    /// Draw local images in terminals that can show them (kitty, iTerm2,
+    /// WezTerm), instead of their alt text
+    #[cfg(feature = "inline-images")]
+    #[arg(long)]
+    inline_images: bool,
+
+    /// Delimiters around link and image text; with --show-urls the
+    /// destination follows in parentheses
+    #[arg(long, value_enum, value_name = "STYLE", default_value_t = LinkBrackets::Square)]
+    link_brackets: LinkBrackets,
+
+    /// Guess the language of code blocks without one, for their label
+    #[arg(long)]
+    detect_language: bool,
+
+    /// Collapse each code block into a one-line summary
+    #[arg(long, overrides_with = "no_fold_code")]
+    fold_code: bool,
+
+    /// Show code blocks in full, overriding an earlier --fold-code
+    #[arg(long, overrides_with = "fold_code")]
+    no_fold_code: bool,
+
+    /// Show only the summary of each `<details>` section, not its content
+    #[arg(long)]
+    collapse_details: bool,
+
+    /// Output width in columns [default: terminal width, then $COLUMNS, then 80]
+    #[arg(short, long, value_name = "COLUMNS")]
+    width: Option<usize>,
+
+    /// In bash, sh and console code blocks, dim `$ ` and `# ` prompts,
+    /// highlight the commands after them and show their output plainly
+    #[arg(long)]
+    shell_prompts: bool,
+
+    /// Give code lines picked out by a fence annotation like ```` ```rust {1,3-5} ```` a background
+    #[arg(long)]
+    highlight_lines: bool,
+
+    /// Dim comments in code blocks whose language is known, by its comment syntax
+    #[arg(long)]
+    dim_comments: bool,
+
+    /// Re-indent minified JSON in ```json code blocks; blocks that don't
+    /// parse are shown as written
+    #[arg(long)]
+    pretty_json: bool,
+
+    /// Show the rows of status badges at the top of a README as one line of
+    /// `[label: value]` chips
+    #[arg(long)]
+    compact_badges: bool,
+
+    /// How to show ```mermaid diagrams
+    #[arg(long, value_enum, value_name = "MODE", default_value_t = MermaidMode::Source)]
+    mermaid: MermaidMode,
+
+    /// Indent the first line of each top-level paragraph by N spaces, book style
+    #[arg(long, value_name = "N")]
+    indent_first_line: Option<usize>,
+
+    /// With --indent-first-line, leave the first paragraph after a heading flush
+    #[arg(long, requires = "indent_first_line")]
+    no_indent_after_heading: bool,
+
+    /// Vertical space between blocks
+    #[arg(long, value_enum, default_value_t = Spacing::Normal)]
+    spacing: Spacing,
+
+    /// Leave more room before more important headings: two blank lines
+    /// before an H1, one before an H2 and none before the rest
+    #[arg(long)]
+    progressive_spacing: bool,
+
+    /// Wrap prose at the output width
+    #[arg(long)]
+    wrap: bool,
+
+    /// With --wrap, indent continuation lines: `hang` lines them up with the
+    /// text after a list marker, `flush` with the block, or N extra spaces
+    #[arg(long, value_name = "hang|flush|N", default_value = "hang", requires = "wrap")]
+    wrap_indent: WrapIndent,
+
+    /// Keep N columns free at the right edge of the output width
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    gutter: usize,
+
+    /// Show tables too wide for the output as one `key | value` block per row
+    #[arg(long)]
+    collapse_tables: bool,
+
+    /// Shade every other table body row, for following rows across wide tables
+    #[arg(long)]
+    zebra: bool,
+
+    /// Lay out emoji as N columns wide (`--wide-emoji=N`, 2 if left out) in
+    /// tables and headings, for terminals that disagree with the built-in widths
+    #[arg(long, value_name = "N", num_args = 0..=1, require_equals = true, default_missing_value = "2")]
+    wide_emoji: Option<usize>,
+
+    /// Narrow the widest table columns, truncating their cells with `…`, so
+    /// tables fit in N columns (`--max-table-width=N`, the output width if left out)
+    #[arg(long, value_name = "N", require_equals = true)]
+    max_table_width: Option<Option<usize>>,
+
+    /// Only render source lines START through END (1-based, inclusive)
+    #[arg(long, value_name = "START:END")]
+    lines: Option<LineRange>,
+
+    /// Scroll through the output with the arrow keys instead of printing it
+    #[cfg(feature = "interactive")]
+    #[arg(short, long, conflicts_with_all = ["output", "watch", "preview_window_safe", "measure"])]
+    interactive: bool,
+
+    /// Also copy the rendered text, without colors, to the system clipboard
+    #[cfg(feature = "clipboard")]
+    #[arg(long)]
+    clipboard: bool,
+
+    /// Copy the rendered text to the system clipboard instead of printing it
+    #[cfg(feature = "clipboard")]
+    #[arg(long, conflicts_with_all = ["clipboard", "output", "watch", "measure"])]
+    clipboard_only: bool,
+
+    /// Start rendering at the first top-level heading with this text
+    #[arg(long, value_name = "HEADING")]
+    since_heading: Option<String>,
+
+    /// Stop rendering before the next top-level heading with this text
+    #[arg(long, value_name = "HEADING")]
+    until_heading: Option<String>,
+
+    /// Only show each heading and the first sentence of the paragraph after it
+    #[arg(long)]
+    summary: bool,
+
+    /// Only render blocks of these kinds, e.g. `code,table`
+    #[arg(long, value_enum, value_name = "TYPES", value_delimiter = ',')]
+    render_only: Option<Vec<BlockKind>>,
+
+    /// Keep running and re-render whenever the file changes
+    #[arg(long)]
+    watch: bool,
+
+    /// Emit no escape sequences but SGR colors, for embedding in preview
+    /// panes: no hyperlinks, no screen clears with --watch, and control
+    /// characters in the source are dropped
+    #[arg(long)]
+    preview_window_safe: bool,
+
+    /// Stop rendering once N bytes of text have been written, ending with an
+    /// `… (output truncated)` notice
+    #[arg(long, value_name = "N")]
+    limit_bytes: Option<usize>,
+
+    /// With --watch, ring the terminal bell (or send a desktop notification
+    /// when built with the `desktop-notify` feature) after each re-render
+    #[arg(long, requires = "watch")]
+    notify: bool,
+
+    /// Character encoding of FILE, e.g. windows-1252 or utf-16le ("auto" reads a
+    /// UTF-8 or UTF-16 byte order mark, falling back to UTF-8)
+    #[arg(long, value_name = "ENCODING", default_value = "auto")]
+    encoding: InputEncoding,
+
+    /// Keep `\r\n` and lone `\r` line endings instead of normalizing them to `\n`
+    #[arg(long)]
+    preserve_crlf: bool,
 }
 
-fn main() -> io::Result<()> {
-    let args = Args::parse();
+/// Sample document shown by `--theme-preview`.
+const THEME_PREVIEW: &str = include_str!("theme_preview.md");
 
-    // 2. File Reading
-    let markdown_input = fs::read_to_string(&args.file)
-        .expect(&format!("Could not read file: {}", args.file));
+impl Args {
+    /// The parser extensions for the input format and the options given.
+    fn parser_options(&self) -> Options {
+        let mut options = self.input_format.options();
+        if self.render_frontmatter_as_table {
+            options |= Options::ENABLE_YAML_STYLE_METADATA_BLOCKS;
+        }
+        if self.show_heading_attributes {
+            options |= Options::ENABLE_HEADING_ATTRIBUTES;
+        }
+        if self.strict || self.math_unicode {
+            options |= Options::ENABLE_MATH; // Found math is reported or converted rather than read as text
+        }
+        if self.glossary {
+            options |= Options::ENABLE_DEFINITION_LIST;
+        }
+        let overrides = [
+            (self.enable_footnotes, Options::ENABLE_FOOTNOTES),
+            (self.enable_tasklists, Options::ENABLE_TASKLISTS),
+            (self.enable_smart_punctuation, Options::ENABLE_SMART_PUNCTUATION),
+            (self.enable_heading_attributes, Options::ENABLE_HEADING_ATTRIBUTES),
+            (self.enable_math, Options::ENABLE_MATH),
+            (self.enable_metadata, Options::ENABLE_YAML_STYLE_METADATA_BLOCKS),
+        ];
+        for (enabled, option) in overrides {
+            options.set(option, enabled.unwrap_or(options.contains(option)));
+        }
+        if self.no_tables {
+            options.remove(Options::ENABLE_TABLES); // Table source then reads as plain paragraphs
+        }
+        options
+    }
 
-    // 3. Markdown Parsing
-    let mut options = Options::empty();
-    options.insert(Options::ENABLE_STRIKETHROUGH);
-    options.insert(Options::ENABLE_TABLES);
-    let parser = MarkdownParser::new_ext(&markdown_input, options);
+    /// Whether output written to stdout is colored: never with --no-color,
+    /// always with --to ansi, and otherwise as the terminal allows.
+    fn color_choice(&self) -> ColorChoice {
+        if self.no_color {
+            ColorChoice::Never
+        } else if self.to == OutputFormat::Ansi {
+            ColorChoice::AlwaysAnsi
+        } else {
+            ColorChoice::Auto
+        }
+    }
+
+    /// The theme at the color depth asked for, or the one `$COLORTERM` suggests.
+    fn theme(&self) -> Theme {
+        #[cfg(feature = "svg")]
+        if self.to == OutputFormat::Svg {
+            // An image isn't limited by the terminal it's made in
+            return Theme::new(self.color_depth.unwrap_or(ColorDepth::Truecolor));
+        }
+        Theme::new(self.color_depth.unwrap_or_else(ColorDepth::detect))
+    }
+
+    /// The character underlining a setext heading at `level`: `=` for H1 and
+    /// `-` for H2, unless `--heading-underline-char` says otherwise.
+    fn heading_underline(&self, level: HeadingLevel) -> char {
+        match (self.heading_underline_char.as_slice(), level) {
+            ([all], _) => *all,
+            ([h1, _, ..], HeadingLevel::H1) => *h1,
+            ([_, h2, ..], _) => *h2,
+            (_, HeadingLevel::H1) => '=',
+            _ => '-',
+        }
+    }
+
+    /// The indentation for one nesting level: a tab, or two spaces with
+    /// `--two-space-indent`.
+    fn indent_unit(&self) -> &'static str {
+        if self.two_space_indent { "  " } else { "\t" }
+    }
+
+    /// The character marking each level of a quoted line: `>`, or the
+    /// `--quote-bar`.
+    fn quote_marker(&self) -> char {
+        self.quote_bar.unwrap_or('>')
+    }
+
+    /// The directory relative image and link paths are resolved against:
+    /// `--base-path`, or the document's own directory.
+    fn base_path(&self) -> PathBuf {
+        match (&self.base_path, &self.file) {
+            (Some(base), _) => PathBuf::from(base),
+            (None, Some(file)) if !self.theme_preview => Path::new(file).parent().map(Path::to_path_buf).unwrap_or_default(),
+            _ => PathBuf::new(),
+        }
+    }
+
+    /// The input as named in messages: the file, or the built-in sample.
+    fn input_name(&self) -> &str {
+        self.file.as_deref().unwrap_or("theme preview")
+    }
+}
+
+/// What the parsed document is rendered to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    /// Colored text for the terminal
+    Terminal,
+    /// HTML, as produced by pulldown-cmark
+    Html,
+    /// Colored text with ANSI escapes even when not writing to a terminal, to
+    /// save and replay later with `cat`
+    Ansi,
+    /// An image of the colored output, as it would look in a terminal
+    #[cfg(feature = "svg")]
+    Svg,
+}
+
+/// Glyphs drawn for task list checkboxes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum CheckboxStyle {
+    /// `[ ]` and `[x]`
+    Ascii,
+    /// `☐` and `☑`
+    Ballot,
+    /// `○` and `●`
+    Circle,
+    /// `⬜` and `✅`
+    Emoji,
+}
 
-    // Initialize a StandardStream for stdout with automatic color detection
-    let mut stdout = StandardStream::stdout(ColorChoice::Auto);
+impl CheckboxStyle {
+    /// The open and the checked box.
+    fn markers(self) -> (&'static str, &'static str) {
+        match self {
+            CheckboxStyle::Ascii => ("[ ]", "[x]"),
+            CheckboxStyle::Ballot => ("☐", "☑"),
+            CheckboxStyle::Circle => ("○", "●"),
+            CheckboxStyle::Emoji => ("⬜", "✅"),
+        }
+    }
+}
 
-    // --- ColorSpec Definitions (remain the same) ---
-    let mut heading_color = ColorSpec::new();
-    heading_color.set_fg(Some(Color::Blue)).set_bold(true);
+/// Glyphs drawn before callout labels.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum CalloutStyle {
+    /// `[i]`, `[*]`, `[!]`, `[!]` and `[x]`
+    Ascii,
+    /// `ℹ️`, `💡`, `❗`, `⚠️` and `🛑`
+    Emoji,
+    /// Octicons from a Nerd Font, as GitHub shows
+    Nerdfont,
+}
 
-    let mut strong_color = ColorSpec::new();
-    strong_color.set_fg(Some(Color::Yellow));
+impl CalloutStyle {
+    /// The icon for a callout of `kind`.
+    fn icon(self, kind: BlockQuoteKind) -> &'static str {
+        match (self, kind) {
+            (CalloutStyle::Ascii, BlockQuoteKind::Note) => "[i]",
+            (CalloutStyle::Ascii, BlockQuoteKind::Tip) => "[*]",
+            (CalloutStyle::Ascii, BlockQuoteKind::Important | BlockQuoteKind::Warning) => "[!]",
+            (CalloutStyle::Ascii, BlockQuoteKind::Caution) => "[x]",
+            (CalloutStyle::Emoji, BlockQuoteKind::Note) => "ℹ️",
+            (CalloutStyle::Emoji, BlockQuoteKind::Tip) => "💡",
+            (CalloutStyle::Emoji, BlockQuoteKind::Important) => "❗",
+            (CalloutStyle::Emoji, BlockQuoteKind::Warning) => "⚠️",
+            (CalloutStyle::Emoji, BlockQuoteKind::Caution) => "🛑",
+            (CalloutStyle::Nerdfont, BlockQuoteKind::Note) => "\u{f449}",
+            (CalloutStyle::Nerdfont, BlockQuoteKind::Tip) => "\u{f400}",
+            (CalloutStyle::Nerdfont, BlockQuoteKind::Important) => "\u{f46e}",
+            (CalloutStyle::Nerdfont, BlockQuoteKind::Warning) => "\u{f421}",
+            (CalloutStyle::Nerdfont, BlockQuoteKind::Caution) => "\u{f46f}",
+        }
+    }
+}
 
-    let mut emphasis_color = ColorSpec::new();
-    emphasis_color.set_fg(Some(Color::Green));
+/// How ordered list items are numbered.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum OrderedStyle {
+    /// `1.`
+    Decimal,
+    /// `a.`
+    LowerAlpha,
+    /// `A.`
+    UpperAlpha,
+    /// `i.`
+    LowerRoman,
+    /// `I.`
+    UpperRoman,
+    /// `1)`
+    Paren,
+}
 
-    let mut strikethrough_color = ColorSpec::new();
-    strikethrough_color.set_fg(Some(Color::Red));
+impl OrderedStyle {
+    /// The styles nested lists step through with --nested-ordered-styles,
+    /// outermost first, as in a formal outline.
+    const OUTLINE: [OrderedStyle; 5] = [
+        OrderedStyle::UpperRoman,
+        OrderedStyle::UpperAlpha,
+        OrderedStyle::Decimal,
+        OrderedStyle::LowerAlpha,
+        OrderedStyle::LowerRoman,
+    ];
 
-    let mut blockquote_color = ColorSpec::new();
-    blockquote_color.set_fg(Some(Color::Magenta));
+    /// The style for a list nested inside `depth` other ordered lists: the
+    /// outline style that many steps on, or this one again for `paren`.
+    fn nested(self, depth: usize) -> Self {
+        match Self::OUTLINE.iter().position(|&style| style == self) {
+            Some(start) => Self::OUTLINE[(start + depth) % Self::OUTLINE.len()],
+            None => self,
+        }
+    }
 
-    let mut code_color = ColorSpec::new();
-    code_color.set_fg(Some(Color::Cyan));
+    /// The marker for item `number`, such as `iv. `. Letters run `a` to `z`
+    /// then `aa`; numbers with no letter or numeral (0) stay decimal.
+    fn marker(self, number: u64) -> String {
+        let label = match self {
+            _ if number == 0 => number.to_string(),
+            OrderedStyle::Decimal | OrderedStyle::Paren => number.to_string(),
+            OrderedStyle::LowerAlpha => alphabetic(number),
+            OrderedStyle::UpperAlpha => alphabetic(number).to_uppercase(),
+            OrderedStyle::LowerRoman => roman(number).to_lowercase(),
+            OrderedStyle::UpperRoman => roman(number),
+        };
+        let delimiter = if self == OrderedStyle::Paren { ')' } else { '.' };
+        format!("{}{} ", label, delimiter)
+    }
+}
 
-    let mut fence_color = ColorSpec::new();
-    fence_color.set_fg(Some(Color::Ansi256(8))); // Dark gray / Bright Black
+/// `number` (from 1) as letters: `a`…`z`, then `aa`, `ab`, ….
+fn alphabetic(mut number: u64) -> String {
+    let mut letters = Vec::new();
+    while number > 0 {
+        number -= 1;
+        letters.push(b'a' + (number % 26) as u8);
+        number /= 26;
+    }
+    letters.iter().rev().map(|&letter| letter as char).collect()
+}
 
-    let mut rule_color = ColorSpec::new();
-    rule_color.set_fg(Some(Color::Ansi256(8))); // Dark gray / Bright Black
+/// `number` (from 1) in Roman numerals.
+fn roman(mut number: u64) -> String {
+    const NUMERALS: [(u64, &str); 13] = [
+        (1000, "M"),
+        (900, "CM"),
+        (500, "D"),
+        (400, "CD"),
+        (100, "C"),
+        (90, "XC"),
+        (50, "L"),
+        (40, "XL"),
+        (10, "X"),
+        (9, "IX"),
+        (5, "V"),
+        (4, "IV"),
+        (1, "I"),
+    ];
+    let mut numeral = String::new();
+    for (value, letters) in NUMERALS {
+        while number >= value {
+            numeral.push_str(letters);
+            number -= value;
+        }
+    }
+    numeral
+}
 
-    let mut table_header_color = ColorSpec::new();
-    table_header_color.set_fg(Some(Color::Ansi256(4))).set_bold(true); // White bold for table headers
+/// Delimiters around link and image text.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum LinkBrackets {
+    /// `[text]`
+    Square,
+    /// Just the text
+    None,
+    /// `⟨text⟩`
+    Angle,
+}
 
-    let mut table_border_color = ColorSpec::new();
-    table_border_color.set_fg(Some(Color::Ansi256(4)));
-    // --- End ColorSpec Definitions ---
+impl LinkBrackets {
+    /// The opening and closing delimiter.
+    fn delimiters(self) -> (&'static str, &'static str) {
+        match self {
+            LinkBrackets::Square => ("[", "]"),
+            LinkBrackets::None => ("", ""),
+            LinkBrackets::Angle => ("⟨", "⟩"),
+        }
+    }
+}
 
-    let mut text_level = 0;
-    let mut in_code_block = false;
-    let mut in_block_quote = false;
-    let mut first_row = false;
-    let mut in_code = false;
-    let mut no_tab = false;
-    let mut in_list = false;
-    let mut in_table = false;
-    let mut table_alignments: Vec<Alignment> = Vec::new();
-    let mut current_row_cells: Vec<String> = Vec::new();
-    let mut is_header_row = false;
-    let mut column_widths: Vec<usize> = Vec::new();
-
-    // 4. Terminal Rendering - This is the core logic with termcolor
-    for event in parser {
+/// How the text of checked task list items is styled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum CompletedTasks {
+    /// Like any other item
+    Plain,
+    /// Dimmed
+    Dim,
+    /// Struck through
+    Strike,
+}
+
+/// How many blank lines separate blocks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum Spacing {
+    /// As few as possible
+    Tight,
+    /// The default
+    Normal,
+    /// One more between blocks, paragraphs included
+    Loose,
+}
+
+/// How `--mermaid` shows ```` ```mermaid ```` diagrams.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum MermaidMode {
+    /// Their source, like any other code block
+    Source,
+    /// A one-line placeholder such as `[mermaid diagram: flowchart, 6 nodes]`
+    Summarize,
+    /// The placeholder followed by the source
+    Both,
+}
+
+/// Kinds of block `--render-only` can pick out.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum BlockKind {
+    /// Code blocks
+    Code,
+    /// Tables
+    Table,
+    /// Headings
+    Heading,
+    /// Blockquotes
+    Quote,
+    /// Lists
+    List,
+}
+
+impl BlockKind {
+    fn matches(self, tag: &Tag) -> bool {
+        matches!(
+            (self, tag),
+            (BlockKind::Code, Tag::CodeBlock(_))
+                | (BlockKind::Table, Tag::Table(_))
+                | (BlockKind::Heading, Tag::Heading { .. })
+                | (BlockKind::Quote, Tag::BlockQuote(_))
+                | (BlockKind::List, Tag::List(_))
+        )
+    }
+}
+
+/// Markdown flavor, selecting which parser extensions are enabled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum InputFormat {
+    /// Strict CommonMark without extensions
+    Commonmark,
+    /// GitHub Flavored Markdown: tables, strikethrough, task lists and footnotes
+    Gfm,
+}
+
+impl InputFormat {
+    /// The pulldown-cmark extensions this flavor enables. Bare URL autolinks
+    /// aren't offered by the parser, so `gfm` only covers `<url>` autolinks,
+    /// which CommonMark already supports.
+    fn options(self) -> Options {
+        match self {
+            InputFormat::Commonmark => Options::empty(),
+            InputFormat::Gfm => {
+                Options::ENABLE_TABLES
+                    | Options::ENABLE_STRIKETHROUGH
+                    | Options::ENABLE_TASKLISTS
+                    | Options::ENABLE_FOOTNOTES
+                    | Options::ENABLE_GFM
+            }
+        }
+    }
+}
+
+/// Whether the document renders to nothing: no events at all, or only raw HTML
+/// blocks (hidden unless they are comments shown with `--show-comments`) and
+/// metadata blocks.
+fn is_effectively_empty(events: &[Event], show_comments: bool) -> bool {
+    let mut html_block = String::new();
+    let mut in_metadata = false;
+    for event in events {
         match event {
-            Event::Start(tag) => {
-                stdout.reset()?;
-                match tag {
-                    Tag::Paragraph => (),
-                    Tag::Heading { level, .. } => {
-                        no_tab = true;
-                        text_level = level as usize - 1 + args.center;
-                        writeln!(stdout)?;
-                        let hash_prefix = "#".repeat(text_level + 1);
-                        let tab_prefix = "\t".repeat(text_level);
-                        stdout.set_color(&heading_color)?;
-                        // write!(stdout, "{}", tab_prefix)?;
-                        if args.symbol {
-                            write!(stdout, "{}{} ", tab_prefix, hash_prefix)?;
-                        } else
-                        {
-                            write!(stdout, "{}", tab_prefix)?;
-                        }
-                    },
-                    Tag::Strong => {
-                        no_tab = true;
-                        stdout.set_color(&strong_color)?;
-                        if args.symbol {
-                            write!(stdout, "**")?;
-                        }
-                    },
-                    Tag::Emphasis => {
-                        no_tab = true;
-                        stdout.set_color(&emphasis_color)?;
-                        if args.symbol {
-                            write!(stdout, "*")?;
-                        }
-                    },
-                    Tag::Strikethrough => {
-                        no_tab = true;
-                        stdout.set_color(&strikethrough_color)?;
-                        if args.symbol {
-                            write!(stdout, "~~")?;
-                        }
-                    },
-                    Tag::BlockQuote(_) => {
-                        in_block_quote = true;
-                        first_row = true;
-                        // no_tab = true;
-                        let tab_prefix = "\t".repeat(text_level);
-                        stdout.set_color(&blockquote_color)?;
-                        write!(stdout, "\n{}> ", tab_prefix)?;
-                    },
-                    Tag::CodeBlock(kind) => {
-                        in_code_block = true;
-                        let lang_str = match kind {
-                            CodeBlockKind::Fenced(lang) => lang.to_string(),
-                            CodeBlockKind::Indented => String::new(),
-                        };
-                        if args.symbol {
-                            // writeln!(stdout)?; // Newline before code block
-                        
-                            let tab_prefix = "\t".repeat(text_level);
-                            write!(stdout, "{}", tab_prefix)?;
-                            stdout.set_color(&fence_color)?; // Set fence color
-                        
-                            write!(stdout, "```")?;
-                            stdout.set_color(&code_color)?; // Set code color for language
-                            write!(stdout, "{}", lang_str)?;
-                        writeln!(stdout)?; // Newline after language info
-                        } else {
-                            stdout.set_color(&code_color)?; // Set code color for language
-                        }
-                    },
-                    Tag::List(_) => {},
-                    Tag::Item => {
-                        in_list = true;
-                        let tab_prefix = "\t".repeat(text_level);
-                        write!(stdout, "{}", tab_prefix)?;
-                        write!(stdout, "- ")?;
-                    },
-                    Tag::Link { .. } => write!(stdout, "[")?,
-                    Tag::Image { .. } => write!(stdout, "![")?,
-                    Tag::Table(alignments) => {
-                        in_table = true;
-                        table_alignments = alignments;
-                        column_widths.clear(); // Clear previous table's widths
-                        current_row_cells.clear(); // Clear any lingering cell data
-                        writeln!(stdout)?; // Newline before table
-                    },
-                    Tag::TableHead => {
-                        is_header_row = true;
-                    },
-                    Tag::TableRow => {
-                        current_row_cells.clear(); // Start a new row, clear previous cells
-                    },
-                    Tag::TableCell => {
-                    },
-                    _ => {}
+            Event::Start(Tag::MetadataBlock(_)) => in_metadata = true,
+            Event::End(TagEnd::MetadataBlock(_)) => in_metadata = false,
+            Event::Text(_) if in_metadata => {}
+            Event::Start(Tag::HtmlBlock) => html_block.clear(),
+            Event::Html(html) => html_block.push_str(html),
+            Event::End(TagEnd::HtmlBlock) => {
+                if show_comments && html_block.trim_start().starts_with("<!--") {
+                    return false;
                 }
-            },
-            Event::End(tag_end) => {
-                match tag_end {
-                    TagEnd::Paragraph => writeln!(stdout)?,
-                    TagEnd::Heading { .. } => {
-                        writeln!(stdout)?; // Newline for the end of the heading
-                        stdout.reset()?; // Reset color after the heading
-                        no_tab = false;
-                    },
-                    TagEnd::Strong => {
-                        if args.symbol {
-                            write!(stdout, "**")?;
-                        }
-                        stdout.reset()?;
-                    },
-                    TagEnd::Emphasis => {
-                        if args.symbol {
-                            write!(stdout, "*")?;
-                        }
-                        stdout.reset()?;
-                    },
-                    TagEnd::Strikethrough => {
-                        if args.symbol {
-                            write!(stdout, "~~")?;
-                        }
-                        stdout.reset()?;
-                    },
-                    TagEnd::BlockQuote(_) => {
-                        writeln!(stdout)?;
-                        in_block_quote = false;
-                        first_row = false;
-                    },  
-                    TagEnd::CodeBlock => {
-                        let tab_prefix = "\t".repeat(text_level);
-                        write!(stdout, "{}", tab_prefix)?;
-                        stdout.set_color(&fence_color)?;
-                        if args.symbol {
-                            write!(stdout, "```")?;
-                        }
-                        writeln!(stdout)?;
-                        in_code_block = false;
-                    },
-                    TagEnd::List(_) => writeln!(stdout)?,
-                    TagEnd::Item => {
-                        writeln!(stdout)?;
-                        in_list = false;
-                    },
-                    TagEnd::Link => write!(stdout, ")")?,
-                    TagEnd::Image => write!(stdout, ")")?,
-                    TagEnd::TableCell => {
-                        // A cell has ended, add its accumulated content to current_row_cells
-                        // We need to capture the text for the cell. This means `Event::Text`
-                        // should append to `current_row_cells` if `in_table` is true.
-                        // For now, let's assume `Event::Text` builds a string directly into `current_row_cells`.
-                        // This will require a minor refactor to Event::Text
-                    },
-                    TagEnd::TableRow => {
-                        // A row has ended. Now we can format and print it.
-                        // First, calculate column widths if this is the header row
-                        for (i, cell_content) in current_row_cells.iter().enumerate() {
-                                if i >= column_widths.len() {
-                                    column_widths.push(0);
-                                }
-                                column_widths[i] = column_widths[i].max(cell_content.len());
-                            }
-                        // if is_header_row {
-                        //     for (i, cell_content) in current_row_cells.iter().enumerate() {
-                        //         if i >= column_widths.len() {
-                        //             column_widths.push(0);
-                        //         }
-                        //         column_widths[i] = column_widths[i].max(cell_content.len());
-                        //     }
-                        // } else {
-                        //     // For body rows, ensure column_widths is populated if it's the first data row
-                        //     // or if header was skipped. Better to calculate overall width after collecting all data
-                        //     // or just ensure column_widths is based on header + max content.
-                        //     // For simplicity, let's assume header defines widths for now.
-                        //     for (i, cell_content) in current_row_cells.iter().enumerate() {
-                        //         if i >= column_widths.len() {
-                        //              // This can happen if the table has no header, or varying cell counts.
-                        //              // For robustness, expand `column_widths` if necessary.
-                        //              column_widths.push(0);
-                        //         }
-                        //         column_widths[i] = column_widths[i].max(cell_content.len());
-                        //     }
-                        // }
-
-                        // Print the row
-                        stdout.set_color(&table_border_color)?;
-                        write!(stdout, "|")?;
-                        stdout.reset()?; // Reset color after the border
-
-                        for (i, cell_content) in current_row_cells.iter().enumerate() {
-                            let width = *column_widths.get(i).unwrap_or(&0);
-                            let formatted_cell = match table_alignments.get(i) {
-                                Some(Alignment::Left) => format!("{:<width$}", cell_content),
-                                Some(Alignment::Center) => format!("{:^width$}", cell_content),
-                                Some(Alignment::Right) => format!("{:>width$}", cell_content),
-                                _ => format!("{:<width$}", cell_content), // Default to left
-                            };
-                            if is_header_row {
-                                stdout.set_color(&table_header_color)?;
-                                write!(stdout, "{}", formatted_cell)?;
-                            } else {
-                                write!(stdout, "{}", formatted_cell)?;
-                            }
-                            stdout.set_color(&table_border_color)?;
-                            write!(stdout, "|")?;
-                            stdout.reset()?;
-                        }
-                        writeln!(stdout)?;
-
-                        if is_header_row {
-                            // Print the header separator line
-                            stdout.set_color(&table_border_color)?;
-                            write!(stdout, "|")?;
-                            for (i, &width) in column_widths.iter().enumerate() {
-                                let separator = match table_alignments.get(i) {
-                                    Some(Alignment::Left) => format!(":{:-<width$}", ""),
-                                    Some(Alignment::Center) => format!(":{:-^width$}", ""),
-                                    Some(Alignment::Right) => format!("{:-<width$}:", ""),
-                                    _ => format!("{:-<width$}", ""), // Default
-                                };
-                                write!(stdout, "{}", separator)?;
-                                write!(stdout, "|")?;
-                            }
-                            writeln!(stdout)?;
-                            stdout.reset()?;
-                            is_header_row = false; // Reset for subsequent rows
-                        }
-                    },
-                    TagEnd::TableHead => {
-                        // The header has ended, table body will follow
-                    },
-                    TagEnd::Table => {
-                        in_table = false;
-                        table_alignments.clear();
-                        column_widths.clear();
-                        writeln!(stdout)?; // Add a newline after the table
-                    },
-                    _ => {}
-                }
-                // Important: Reset color after any closing tag that might have applied specific styling
-                // We're moving this to be handled by individual End tags where necessary, or implicitly by the next Start tag.
-                // For safety, let's keep it here for now if no specific reset happened in the match arm.
-                // Or, better, strategically reset in each End arm.
-            },
-            Event::Text(text) => {
-                if in_table {
-                    // When in a table, accumulate text for the current cell
-                    // This assumes that all text events for a single cell come consecutively
-                    // and that `TableCell` start/end delineate cells.
-                    // This is a simplification; a robust solution might involve a temporary
-                    // buffer for cell content.
-                    if current_row_cells.is_empty() { // Need to re-think this.
-                        // If it's the first text in a new cell, or after a cell end.
-                        write!(stdout, "{}", text)?;
-                        current_row_cells.push(text.to_string());
-                    } else {
-                        // Append to the last cell
-                        let last_idx = current_row_cells.len() - 1;
-                        current_row_cells[last_idx].push_str(&text);
-                    }
-                } else {
-                    if !in_list && !no_tab && !in_block_quote && !in_code{
-                        let tab_prefix = "\t".repeat(text_level);
-                        write!(stdout, "{}", tab_prefix)?;
-                    }
-                    if in_block_quote && first_row && !in_code {
-                        first_row = false;
-                    } else if in_block_quote && !no_tab && !in_code {
-                        let tab_prefix = "\t".repeat(text_level);
-                        write!(stdout, "{}  ", tab_prefix)?;
-                    } 
-                    if in_code {
-                        in_code = false;
-                        write!(stdout, "~", )?;
-                    }
-                    write!(stdout, "{}", text)?;
-                }
-            },
-            Event::Code(code) => {
-                if in_table {
-                    // Handle inline code within tables if needed, currently not accumulating
-                    // This adds complexity as `current_row_cells` stores `String`, and `Code` events
-                    // also carry content. For a simple CLI, we might just print it or convert to string.
-                    if current_row_cells.is_empty() {
-                         current_row_cells.push(format!("`{}`", code));
-                    } else {
-                        let last_idx = current_row_cells.len() - 1;
-                        current_row_cells[last_idx].push_str(&format!("`{}`", code));
-                    }
-                } else {
-                    if in_code_block {
-                        let tab_prefix = "\t".repeat(text_level);
-                        write!(stdout, "{}", tab_prefix)?;
-                    } else {
-                        in_code = true;
-                    }
-                    stdout.set_color(&code_color)?;
-                    if args.symbol {
-                        write!(stdout, "`{}`", code)?;
-                    } else {
-                        write!(stdout, "{}", code)?;
-                    }
-                    stdout.reset()?;
-                }
-            },
-            Event::SoftBreak => {
-                if in_table {
-                    // Soft breaks within table cells usually mean space
-                    if let Some(last_cell) = current_row_cells.last_mut() {
-                        last_cell.push(' ');
-                    }
-                } else {
-                    writeln!(stdout)?;
-                    in_list= false;
-                    // write!(stdout, " ")?;
-                }
-            },
-            Event::HardBreak => {
-                if in_table {
-                    // Hard breaks within table cells might mean a newline, or be ignored
-                    if let Some(last_cell) = current_row_cells.last_mut() {
-                        last_cell.push('\n'); // Or just a space depending on desired rendering
-                    }
-                } else {
-                    writeln!(stdout)?;
-                }
-            },
-            Event::Rule => {
-                writeln!(stdout)?;
-                stdout.set_color(&rule_color)?;
-                let rule = "---".repeat(text_level + 1);
-                let tab_prefix = "\t".repeat(text_level);
-                write!(stdout, "{}{}", tab_prefix, rule)?;
-                writeln!(stdout)?;
-                stdout.reset()?;
-            },
-            Event::FootnoteReference(name) => write!(stdout, "[^{}]", name)?,
+            }
+            _ => return false,
+        }
+    }
+    true
+}
+
+/// Whitespace-separated words in the document's prose: headings, paragraphs,
+/// lists, quotes and table cells, but not code, raw HTML or metadata.
+fn count_words(events: &[Event]) -> usize {
+    let mut prose = String::new();
+    let mut in_code_block = false;
+    let mut in_metadata = false;
+    for event in events {
+        match event {
+            Event::Start(Tag::CodeBlock(_)) => in_code_block = true,
+            Event::End(TagEnd::CodeBlock) => in_code_block = false,
+            Event::Start(Tag::MetadataBlock(_)) => in_metadata = true,
+            Event::End(TagEnd::MetadataBlock(_)) => in_metadata = false,
+            // Text can arrive in several chunks, so join it before splitting
+            Event::Text(text) if !in_code_block && !in_metadata => prose.push_str(text),
+            Event::SoftBreak | Event::HardBreak | Event::Code(_) => prose.push(' '),
+            Event::End(TagEnd::Paragraph | TagEnd::Heading(_) | TagEnd::Item | TagEnd::TableCell) => {
+                prose.push(' ')
+            }
             _ => {}
         }
+    }
+    // Punctuation left over around inline code isn't a word
+    prose.split_whitespace().filter(|word| word.chars().any(char::is_alphanumeric)).count()
+}
+
+fn main() -> io::Result<()> {
+    let args = Args::parse();
+    if let Some(width) = args.wide_emoji {
+        term::set_emoji_width(width);
+    }
+    let result = if args.list_supported_elements {
+        let mut stdout = ResetOnDrop::new(StandardStream::stdout(args.color_choice()));
+        elements::list(&mut stdout, &args)
+    } else {
+        if args.watch { watch::watch(&args, run) } else { run(&args) }
+    };
+    match result {
+        // The reader went away (e.g. `md-preview big.md | head`): stop quietly,
+        // with the status a process killed by SIGPIPE would have
+        Err(error) if error.kind() == io::ErrorKind::BrokenPipe => std::process::exit(141),
+        Err(error) => {
+            eprintln!("md-preview: {}", error);
+            std::process::exit(1);
+        }
+        Ok(()) => Ok(()),
+    }
+}
+
+/// Read the document and render it once, or check it with --validate.
+fn run(args: &Args) -> io::Result<()> {
+    // 2. File Reading
+    let started = Instant::now();
+    let mut markdown_input = match &args.file {
+        _ if args.theme_preview => THEME_PREVIEW.to_string(),
+        Some(file) => {
+            let bytes = fs::read(file).map_err(|error| io::Error::new(error.kind(), format!("could not read {}: {}", file, error)))?;
+            source::decode(&bytes, args.encoding)
+        }
+        None => unreachable!("clap requires FILE without --theme-preview"),
+    };
+    if !args.preserve_crlf {
+        markdown_input = source::normalize_line_endings(&markdown_input);
+    }
+    if let Some(range) = args.lines {
+        markdown_input = source::select_lines(&markdown_input, range);
+    }
+    let (markdown_input, abbreviations) = source::extract_abbreviations(&markdown_input);
+
+    let read_time = started.elapsed();
+
+    if args.validate {
+        let warnings = validate::check(&markdown_input, args.parser_options(), &args.base_path());
+        for warning in &warnings {
+            eprintln!("{}:{}", args.input_name(), warning);
+        }
+        if args.render {
+            render_document(args, &markdown_input, &abbreviations, read_time)?;
+        }
+        return match warnings.len() {
+            0 => Ok(()),
+            1 => Err(io::Error::other("1 warning")),
+            n => Err(io::Error::other(format!("{} warnings", n))),
+        };
+    }
+    render_document(args, &markdown_input, &abbreviations, read_time)
+}
+
+/// Parse and render the document read by `run`.
+fn render_document(args: &Args, markdown_input: &str, abbreviations: &[(String, String)], read_time: Duration) -> io::Result<()> {
+    // 3. Markdown Parsing
+    let options = args.parser_options();
+
+    if args.dump_events {
+        // Written with `?` rather than println! so a closed pipe isn't a panic
+        let mut out = io::stdout().lock();
+        let parser = MarkdownParser::new_ext(markdown_input, options);
+        if args.source_ranges {
+            // Offsets are only tracked here so the normal path keeps the plain parser
+            for (event, range) in parser.into_offset_iter() {
+                let line = markdown_input[..range.start].matches('\n').count() + 1;
+                writeln!(out, "L{} {:?} {:?}", line, range, event)?;
+            }
+        } else {
+            for event in parser {
+                writeln!(out, "{:?}", event)?;
+            }
+        }
+        return Ok(());
+    }
+
+    // In symbol mode, remember which headings were written setext-style so they
+    // round-trip as underlines instead of gaining `#` hashes.
+    let mut setext_headings: Vec<bool> = if args.symbol {
+        MarkdownParser::new_ext(markdown_input, options)
+            .into_offset_iter()
+            .filter_map(|(event, range)| match event {
+                Event::Start(Tag::Heading { .. }) => {
+                    Some(!markdown_input[range].trim_start().starts_with('#'))
+                }
+                _ => None,
+            })
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    let started = Instant::now();
+    let mut events: Vec<Event> = MarkdownParser::new_ext(markdown_input, options).collect();
+    let parse_time = started.elapsed();
+
+    if args.since_heading.is_some() || args.until_heading.is_some() {
+        let range = filter::heading_range(&events, args.since_heading.as_deref(), args.until_heading.as_deref())?;
+        let skipped = events[..range.start].iter().filter(|event| matches!(event, Event::Start(Tag::Heading { .. }))).count();
+        setext_headings.drain(..skipped.min(setext_headings.len()));
+        events.truncate(range.end);
+        events.drain(..range.start);
+    }
+    if args.summary {
+        events = filter::summary(events, &mut setext_headings);
+    }
+    if let Some(kinds) = &args.render_only {
+        events = filter::blocks(events, &mut setext_headings, |tag| kinds.iter().any(|kind| kind.matches(tag)));
+    }
+
+    if args.pretty_json {
+        events = json::prettify(events);
+    }
+    if args.compact_badges {
+        events = badges::compact(events);
+    }
+
+    if args.word_count_only {
+        return writeln!(io::stdout(), "{}", count_words(&events));
+    }
+    let started = Instant::now();
+
+    // Initialize a StandardStream for stdout with automatic color detection,
+    // or write uncolored output when sending it to a file. With --interactive
+    // the output is rendered off-screen and paged through afterwards, and
+    // with --measure it is rendered off-screen only to be sized. With
+    // --to svg it is recorded with its colors to be drawn.
+    let mut measured = Ansi::new(Vec::new());
+    #[cfg(feature = "svg")]
+    let mut recorded = svg::Recorder::default();
+    #[cfg(feature = "interactive")]
+    let mut paged = termcolor::Ansi::new(Vec::new());
+    #[cfg(feature = "clipboard")]
+    let mut copied = Vec::new();
+    let mut stdout: Box<dyn WriteColor + '_> = match &args.output {
+        #[cfg(feature = "svg")]
+        _ if args.to == OutputFormat::Svg => Box::new(&mut recorded),
+        Some(path) if args.to == OutputFormat::Ansi => Box::new(Ansi::new(fs::File::create(path)?)),
+        Some(path) => Box::new(NoColor::new(fs::File::create(path)?)),
+        None if args.measure => Box::new(&mut measured),
+        #[cfg(feature = "interactive")]
+        None if args.interactive => Box::new(&mut paged),
+        #[cfg(feature = "clipboard")]
+        None if args.clipboard_only => Box::new(NoColor::new(io::sink())),
+        None if args.show_escapes => Box::new(Ansi::new(ShowEscapes::new(io::stdout()))),
+        None if args.tables_as_csv => Box::new(StandardStream::stdout(ColorChoice::Never)),
+        None => Box::new(StandardStream::stdout(args.color_choice())),
+    };
+    if args.preview_window_safe {
+        stdout = Box::new(PreviewSafe::new(stdout));
+    }
+    if args.show_tabs {
+        stdout = Box::new(ShowTabs::new(stdout));
+    }
+    #[cfg(feature = "clipboard")]
+    if args.clipboard || args.clipboard_only {
+        stdout = Box::new(output::CopyTo::new(stdout, &mut copied));
+    }
+    let truncated = Rc::new(Cell::new(false));
+    if let Some(limit) = args.limit_bytes {
+        stdout = Box::new(LimitBytes::new(stdout, limit, Rc::clone(&truncated)));
+    }
+    let mut stdout = ResetOnDrop::new(stdout);
+
+    if args.to == OutputFormat::Html {
+        let mut html_output = String::new();
+        html::push_html(&mut html_output, events.into_iter());
+        stdout.write_all(html_output.as_bytes())?;
         stdout.flush()?;
+        drop(stdout);
+    } else {
+        if is_effectively_empty(&events, args.show_comments) {
+            if args.verbose {
+                eprintln!("{}: empty document", args.input_name());
+            }
+            if args.measure {
+                writeln!(io::stdout(), "0x0")?;
+            }
+            return Ok(());
+        }
+
+        // 4. Terminal Rendering
+        let code_languages = if args.detect_language { detect::code_languages(&events) } else { Vec::new() };
+        let mermaid = if args.mermaid == MermaidMode::Source { Vec::new() } else { mermaid::summaries(&events) };
+        let regions = if args.label_regions { regions::boundaries(&events, args.render_frontmatter_as_table) } else { Vec::new() };
+        let mut regions = regions.into_iter().peekable();
+        let abbreviations = if args.abbr { abbreviations.to_vec() } else { Vec::new() };
+        let progress = if args.progress || args.progress_only { progress::counts(&events) } else { Vec::new() };
+        let anchors = if args.check_anchors { Some(validate::heading_slugs(&events)) } else { None };
+        let glossary = if args.glossary { glossary::terms(&events) } else { Vec::new() };
+        let toc = if args.render_toc_with_links { validate::headings(&events) } else { Vec::new() };
+        let mut renderer = Renderer::new(
+            stdout,
+            args,
+            setext_headings,
+            code_languages,
+            mermaid,
+            abbreviations,
+            progress,
+            anchors,
+            glossary,
+        );
+        if args.render_toc_with_links {
+            renderer.contents(&toc)?;
+        }
+        for (i, event) in events.into_iter().enumerate() {
+            if truncated.get() {
+                break;
+            }
+            if let Some((_, region)) = regions.next_if(|&(start, _)| start == i) {
+                renderer.label_region(region)?;
+                if region == Region::Footer {
+                    continue; // The labelled separator stands in for the footer's rule
+                }
+            }
+            renderer.handle(event)?;
+        }
+        renderer.finish()?;
+        let degraded = renderer.degraded().to_vec();
+        drop(renderer);
+        if args.strict && !degraded.is_empty() {
+            for (what, count) in &degraded {
+                eprintln!("{}: {} × {} not rendered faithfully", args.input_name(), count, what);
+            }
+            return Err(io::Error::other("--strict: the document uses constructs that can't be rendered faithfully"));
+        }
+
+        if args.time {
+            eprintln!("read:   {:?}", read_time);
+            eprintln!("parse:  {:?}", parse_time);
+            eprintln!("render: {:?}", started.elapsed());
+        }
     }
 
-    // Reset colors one last time at the end of the entire parsing process
-    stdout.reset()?;
+    if args.measure {
+        let (width, height) = term::measure(&String::from_utf8_lossy(measured.get_ref()));
+        writeln!(io::stdout(), "{}x{}", width, height)?;
+    }
+    #[cfg(feature = "svg")]
+    if args.to == OutputFormat::Svg {
+        let image = recorded.to_svg(&args.theme());
+        match &args.output {
+            Some(path) => fs::write(path, image)?,
+            None => io::stdout().write_all(image.as_bytes())?,
+        }
+    }
+    #[cfg(feature = "interactive")]
+    if args.interactive {
+        pager::page(paged.get_ref())?;
+    }
+    #[cfg(feature = "clipboard")]
+    if args.clipboard || args.clipboard_only {
+        clipboard::copy(&String::from_utf8_lossy(&copied), args.clipboard_only)?;
+    }
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_empty(markdown: &str, show_comments: bool) -> bool {
+        let events: Vec<Event> = MarkdownParser::new_ext(markdown, Options::ENABLE_YAML_STYLE_METADATA_BLOCKS).collect();
+        is_effectively_empty(&events, show_comments)
+    }
+
+    #[test]
+    fn empty_and_whitespace_only() {
+        assert!(is_empty("", false));
+        assert!(is_empty("  \n\n\t\n   \n", false));
+    }
+
+    #[test]
+    fn comment_only() {
+        assert!(is_empty("<!-- nothing -->\n\n<!--\n  or here\n-->\n", false));
+        assert!(!is_empty("<!-- shown -->\n", true));
+    }
+
+    #[test]
+    fn front_matter_only() {
+        assert!(is_empty("---\ntitle: Only metadata\n---\n", false));
+    }
+
+    #[test]
+    fn content_is_not_empty() {
+        assert!(!is_empty("text", false));
+        assert!(!is_empty("<!-- note -->\n\n# Heading\n", false));
+    }
+}
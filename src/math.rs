@@ -0,0 +1,134 @@
+//! `--math-unicode`: simple LaTeX math written with Unicode symbols, for
+//! formulas that don't need a real math engine.
+
+use crate::script::Script;
+
+/// The LaTeX commands replaced, by name without the backslash: Greek
+/// letters, relations, operators and arrows. Anything else is left as written.
+const SYMBOLS: &[(&str, &str)] = &[
+    ("alpha", "α"),
+    ("beta", "β"),
+    ("gamma", "γ"),
+    ("delta", "δ"),
+    ("epsilon", "ε"),
+    ("varepsilon", "ε"),
+    ("zeta", "ζ"),
+    ("eta", "η"),
+    ("theta", "θ"),
+    ("iota", "ι"),
+    ("kappa", "κ"),
+    ("lambda", "λ"),
+    ("mu", "μ"),
+    ("nu", "ν"),
+    ("xi", "ξ"),
+    ("pi", "π"),
+    ("rho", "ρ"),
+    ("sigma", "σ"),
+    ("tau", "τ"),
+    ("upsilon", "υ"),
+    ("phi", "φ"),
+    ("varphi", "φ"),
+    ("chi", "χ"),
+    ("psi", "ψ"),
+    ("omega", "ω"),
+    ("Gamma", "Γ"),
+    ("Delta", "Δ"),
+    ("Theta", "Θ"),
+    ("Lambda", "Λ"),
+    ("Xi", "Ξ"),
+    ("Pi", "Π"),
+    ("Sigma", "Σ"),
+    ("Phi", "Φ"),
+    ("Psi", "Ψ"),
+    ("Omega", "Ω"),
+    ("leq", "≤"),
+    ("le", "≤"),
+    ("geq", "≥"),
+    ("ge", "≥"),
+    ("neq", "≠"),
+    ("ne", "≠"),
+    ("approx", "≈"),
+    ("equiv", "≡"),
+    ("sim", "∼"),
+    ("propto", "∝"),
+    ("times", "×"),
+    ("cdot", "·"),
+    ("div", "÷"),
+    ("pm", "±"),
+    ("mp", "∓"),
+    ("infty", "∞"),
+    ("partial", "∂"),
+    ("nabla", "∇"),
+    ("sum", "∑"),
+    ("prod", "∏"),
+    ("int", "∫"),
+    ("sqrt", "√"),
+    ("in", "∈"),
+    ("notin", "∉"),
+    ("subset", "⊂"),
+    ("subseteq", "⊆"),
+    ("cup", "∪"),
+    ("cap", "∩"),
+    ("emptyset", "∅"),
+    ("forall", "∀"),
+    ("exists", "∃"),
+    ("neg", "¬"),
+    ("land", "∧"),
+    ("lor", "∨"),
+    ("to", "→"),
+    ("rightarrow", "→"),
+    ("leftarrow", "←"),
+    ("Rightarrow", "⇒"),
+    ("Leftarrow", "⇐"),
+    ("iff", "⇔"),
+    ("mapsto", "↦"),
+    ("ldots", "…"),
+    ("cdots", "⋯"),
+    ("circ", "∘"),
+    ("degree", "°"),
+];
+
+/// `latex` with the commands in `SYMBOLS` replaced and `^`/`_` scripts
+/// raised or lowered where every character has a glyph. What can't be
+/// converted stays as written.
+pub fn to_unicode(latex: &str) -> String {
+    let mut converted = String::with_capacity(latex.len());
+    let mut rest = latex;
+    while let Some(ch) = rest.chars().next() {
+        rest = &rest[ch.len_utf8()..];
+        match ch {
+            '\\' => {
+                let name_len = rest.find(|c: char| !c.is_ascii_alphabetic()).unwrap_or(rest.len());
+                let name = &rest[..name_len];
+                match SYMBOLS.iter().find(|&&(command, _)| command == name) {
+                    Some((_, symbol)) => {
+                        converted.push_str(symbol);
+                        rest = &rest[name_len..];
+                    }
+                    None => converted.push('\\'),
+                }
+            }
+            '^' | '_' => {
+                // A `{group}` without nested braces, or a single character
+                let operand = match rest.strip_prefix('{') {
+                    Some(inner) => inner.find('}').map(|end| (&inner[..end], true, &inner[end + 1..])),
+                    None => rest.chars().next().map(|next| (&rest[..next.len_utf8()], false, &rest[next.len_utf8()..])),
+                };
+                let Some((operand, braced, after)) = operand.filter(|(operand, _, _)| !operand.contains('{')) else {
+                    converted.push(ch);
+                    continue;
+                };
+                rest = after;
+                let operand = to_unicode(operand);
+                let script = if ch == '^' { Script::Super } else { Script::Sub };
+                match script.glyphs(&operand) {
+                    Some(glyphs) => converted.push_str(&glyphs),
+                    None if braced => converted.push_str(&format!("{}{{{}}}", ch, operand)),
+                    None => converted.push_str(&format!("{}{}", ch, operand)),
+                }
+            }
+            _ => converted.push(ch),
+        }
+    }
+    converted
+}
@@ -0,0 +1,142 @@
+//! `--mermaid`: one-line summaries of ```` ```mermaid ```` diagrams, worked out
+//! heuristically from their source.
+
+use std::collections::HashSet;
+
+use pulldown_cmark::{CodeBlockKind, Event, Tag, TagEnd};
+
+/// Flowchart lines that style or group nodes rather than declaring them.
+const FLOWCHART_KEYWORDS: &[&str] = &["subgraph", "end", "style", "classDef", "class", "click", "linkStyle", "direction"];
+
+/// Sequence diagram message arrows, longest first.
+const SEQUENCE_ARROWS: &[&str] = &["-->>", "->>", "-->", "->", "--x", "-x", "--)", "-)"];
+
+/// The node ids on a flowchart line such as `A[Start] -->|yes| B & C`.
+fn flowchart_nodes(line: &str, nodes: &mut HashSet<String>) {
+    let mut chunk = String::new();
+    let mut chars = line.chars().peekable();
+    let mut chunks = Vec::new();
+    while let Some(ch) = chars.next() {
+        if matches!(ch, '-' | '=' | '.') && matches!(chars.peek(), Some('-' | '=' | '.' | '>')) {
+            // An arrow, possibly followed by a `|label|`
+            while chars.next_if(|c| matches!(c, '-' | '=' | '.' | '>')).is_some() {}
+            if chars.next_if_eq(&'|').is_some() {
+                while chars.next().is_some_and(|c| c != '|') {}
+            }
+            chunks.push(std::mem::take(&mut chunk));
+        } else {
+            chunk.push(ch);
+        }
+    }
+    chunks.push(chunk);
+    for node in chunks.iter().flat_map(|chunk| chunk.split('&')) {
+        // The id ends where the shape (`[text]`, `(text)`, `{text}`, ...) begins
+        let id: String = node.trim().chars().take_while(|c| c.is_alphanumeric() || *c == '_').collect();
+        if !id.is_empty() {
+            nodes.insert(id);
+        }
+    }
+}
+
+/// The participants named on a sequence diagram line such as
+/// `participant A as Alice` or `A->>B: Hello`.
+fn sequence_participants(line: &str, participants: &mut HashSet<String>) {
+    if let Some(rest) = line.strip_prefix("participant ").or_else(|| line.strip_prefix("actor ")) {
+        let name = rest.split(" as ").next().unwrap_or(rest).trim();
+        participants.insert(name.to_string());
+        return;
+    }
+    let Some((message, _)) = line.split_once(':') else {
+        return;
+    };
+    let Some(arrow) = message.find('-') else {
+        return;
+    };
+    let (from, rest) = message.split_at(arrow);
+    let Some(to) = SEQUENCE_ARROWS.iter().find_map(|arrow| rest.strip_prefix(arrow)) else {
+        return;
+    };
+    // `+` and `-` after the arrow toggle activation boxes
+    let to = to.trim_start_matches(['+', '-']);
+    for name in [from, to] {
+        if !name.trim().is_empty() {
+            participants.insert(name.trim().to_string());
+        }
+    }
+}
+
+/// A summary like `flowchart, 6 nodes` for a diagram's source.
+pub fn summarize(source: &str) -> String {
+    let mut lines = source.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with("%%"));
+    let Some(header) = lines.next() else {
+        return "empty".to_string();
+    };
+    let kind = header.split_whitespace().next().unwrap_or_default();
+    let count = |items: HashSet<String>, one: &str, many: &str| {
+        format!("{} {}", items.len(), if items.len() == 1 { one } else { many })
+    };
+    match kind {
+        "graph" | "flowchart" => {
+            let mut nodes = HashSet::new();
+            for line in lines {
+                if !FLOWCHART_KEYWORDS.contains(&line.split_whitespace().next().unwrap_or_default()) {
+                    flowchart_nodes(line, &mut nodes);
+                }
+            }
+            format!("flowchart, {}", count(nodes, "node", "nodes"))
+        }
+        "sequenceDiagram" => {
+            let mut participants = HashSet::new();
+            for line in lines {
+                sequence_participants(line, &mut participants);
+            }
+            format!("sequence diagram, {}", count(participants, "participant", "participants"))
+        }
+        "classDiagram" => {
+            let classes = lines.filter_map(|line| line.strip_prefix("class ")).map(|rest| {
+                rest.split(|c: char| !c.is_alphanumeric() && c != '_').next().unwrap_or_default().to_string()
+            });
+            format!("class diagram, {}", count(classes.collect(), "class", "classes"))
+        }
+        _ => {
+            let name = match kind {
+                "stateDiagram" | "stateDiagram-v2" => "state diagram",
+                "erDiagram" => "entity relationship diagram",
+                "gantt" => "gantt chart",
+                "pie" => "pie chart",
+                "gitGraph" => "git graph",
+                "journey" => "user journey",
+                other => other,
+            };
+            let lines = lines.count();
+            format!("{}, {} line{}", name, lines, if lines == 1 { "" } else { "s" })
+        }
+    }
+}
+
+/// For each code block in the document, in order, the summary of its diagram
+/// if it is a `mermaid` block.
+pub fn summaries(events: &[Event]) -> Vec<Option<String>> {
+    let mut summaries = Vec::new();
+    let mut diagram: Option<String> = None;
+    for event in events {
+        match event {
+            Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(lang))) if lang.split_whitespace().next() == Some("mermaid") => {
+                diagram = Some(String::new())
+            }
+            Event::Start(Tag::CodeBlock(_)) => summaries.push(None),
+            Event::Text(text) => {
+                if let Some(source) = &mut diagram {
+                    source.push_str(text);
+                }
+            }
+            Event::End(TagEnd::CodeBlock) => {
+                if let Some(source) = diagram.take() {
+                    summaries.push(Some(summarize(&source)));
+                }
+            }
+            _ => {}
+        }
+    }
+    summaries
+}
@@ -0,0 +1,625 @@
+//! Writer wrappers around the final output stream.
+
+use std::cell::Cell;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+use termcolor::{ColorSpec, HyperlinkSpec, WriteColor};
+use unicode_width::UnicodeWidthChar;
+
+use crate::term;
+
+/// Output held back by `LineTracker::capture`.
+enum Captured {
+    Text(String),
+    Color(ColorSpec),
+    Reset,
+}
+
+/// Output held back by `LineTracker` after the last text written, so the
+/// blank lines at the end of the document can be dropped.
+enum Trailing {
+    Newline,
+    Color(ColorSpec),
+    Reset,
+}
+
+/// Output stream wrapper that remembers whether anything was written, the
+/// column the next character lands in, and the color currently set. It can
+/// also hold output back to lay it out once a whole block is known.
+pub struct LineTracker<W> {
+    inner: W,
+    written: bool,
+    at_line_start: bool,
+    column: usize,
+    color: Option<ColorSpec>,
+    captured: Option<Vec<Captured>>,
+    trailing: Vec<Trailing>,
+    blank_lines: usize, // Requested by `blank_lines`, written once more text follows
+    inner_at_line_start: bool, // Whether the last byte passed on was a newline
+}
+
+impl<W> LineTracker<W> {
+    pub fn new(inner: W) -> Self {
+        LineTracker {
+            inner,
+            written: false,
+            at_line_start: true,
+            column: 0,
+            color: None,
+            captured: None,
+            trailing: Vec::new(),
+            blank_lines: 0,
+            inner_at_line_start: true,
+        }
+    }
+
+    /// Hold back everything written from now on until `write_centered`.
+    pub fn capture(&mut self) {
+        self.captured.get_or_insert_with(Vec::new);
+    }
+
+    /// Display column of the next character written, with tab stops every 8.
+    pub fn column(&self) -> usize {
+        self.column
+    }
+
+    /// The color set since the last reset, if any.
+    pub fn color(&self) -> Option<&ColorSpec> {
+        self.color.as_ref()
+    }
+
+    /// Whether any text has been written yet.
+    pub fn written(&self) -> bool {
+        self.written
+    }
+
+    /// Whether the next byte written would start a new line.
+    pub fn at_line_start(&self) -> bool {
+        self.at_line_start
+    }
+
+    /// Separate the next text from the last with at least `count` blank
+    /// lines. Requests made before the next text share the larger count.
+    pub fn blank_lines(&mut self, count: usize) {
+        self.blank_lines = self.blank_lines.max(count);
+    }
+
+    /// Queue the requested blank lines after the newline that ended the last
+    /// line, before any colors set for the next text.
+    fn take_blank_lines(&mut self) {
+        let count = std::mem::take(&mut self.blank_lines);
+        if count == 0 {
+            return;
+        }
+        match &mut self.captured {
+            Some(captured) => captured.push(Captured::Text("\n".repeat(count))),
+            None => {
+                let at = self.trailing.iter().rposition(|item| matches!(item, Trailing::Newline)).map_or(0, |last| last + 1);
+                self.trailing.splice(at..at, (0..count).map(|_| Trailing::Newline));
+            }
+        }
+    }
+}
+
+impl<W: WriteColor> LineTracker<W> {
+    /// Pass on the output held back after the last text, now that more follows.
+    fn release_trailing(&mut self) -> io::Result<()> {
+        for item in std::mem::take(&mut self.trailing) {
+            match item {
+                Trailing::Newline => {
+                    self.inner.write_all(b"\n")?;
+                    self.inner_at_line_start = true;
+                }
+                Trailing::Color(spec) => self.inner.set_color(&spec)?,
+                Trailing::Reset => self.inner.reset()?,
+            }
+        }
+        Ok(())
+    }
+
+    /// End the output: the blank lines held back are dropped and the colors
+    /// reset, then a single newline ends the last line. With `newline` false,
+    /// that newline is only written if the output already ended with one.
+    pub fn end(&mut self, newline: bool) -> io::Result<()> {
+        self.trailing.clear();
+        self.blank_lines = 0;
+        self.reset()?;
+        if self.written && !self.inner_at_line_start && (newline || self.at_line_start) {
+            self.inner.write_all(b"\n")?;
+            self.inner_at_line_start = true;
+            self.at_line_start = true;
+            self.column = 0;
+        }
+        Ok(())
+    }
+
+    /// Write out the captured output with each line centered in `width`
+    /// columns, its own indentation dropped. If any line is too wide to fit,
+    /// the output is written unchanged instead.
+    pub fn write_centered(&mut self, width: usize) -> io::Result<()> {
+        let Some(captured) = self.captured.take() else {
+            return Ok(());
+        };
+        let mut lines: Vec<Vec<Captured>> = vec![Vec::new()];
+        for item in captured {
+            match item {
+                Captured::Text(text) => {
+                    for (i, piece) in text.split('\n').enumerate() {
+                        if i > 0 {
+                            lines.push(Vec::new());
+                        }
+                        if !piece.is_empty() {
+                            lines.last_mut().unwrap().push(Captured::Text(piece.to_string()));
+                        }
+                    }
+                }
+                other => lines.last_mut().unwrap().push(other),
+            }
+        }
+        let line_width = |line: &[Captured]| {
+            let text: String = line.iter().filter_map(|item| match item {
+                Captured::Text(text) => Some(text.as_str()),
+                _ => None,
+            }).collect();
+            term::text_width(text.trim())
+        };
+        let center = lines.iter().all(|line| line_width(line) <= width);
+
+        for (i, line) in lines.iter().enumerate() {
+            if i > 0 {
+                writeln!(self)?;
+            }
+            let mut leading = center && line_width(line) > 0;
+            for item in line {
+                match item {
+                    Captured::Text(text) if leading => {
+                        let text = text.trim_start();
+                        if !text.is_empty() {
+                            write!(self, "{:1$}", "", (width - line_width(line)) / 2)?;
+                            leading = false;
+                        }
+                        write!(self, "{}", text)?;
+                    }
+                    Captured::Text(text) => write!(self, "{}", text)?,
+                    Captured::Color(spec) => self.set_color(spec)?,
+                    Captured::Reset => self.reset()?,
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<W: WriteColor> Write for LineTracker<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if !buf.is_empty() {
+            self.take_blank_lines();
+        }
+        let n = match &mut self.captured {
+            Some(captured) => {
+                captured.push(Captured::Text(String::from_utf8_lossy(buf).into_owned()));
+                buf.len()
+            }
+            None => {
+                // Newlines at the end are held back until more text follows
+                let text_len = buf.iter().rposition(|&b| b != b'\n').map_or(0, |last| last + 1);
+                if text_len > 0 {
+                    self.release_trailing()?;
+                    self.inner.write_all(&buf[..text_len])?;
+                    self.inner_at_line_start = false;
+                }
+                self.trailing.extend((text_len..buf.len()).map(|_| Trailing::Newline));
+                buf.len()
+            }
+        };
+        if n > 0 {
+            self.written = true;
+            self.at_line_start = buf[n - 1] == b'\n';
+        }
+        for ch in String::from_utf8_lossy(&buf[..n]).chars() {
+            self.column = match ch {
+                '\n' => 0,
+                '\t' => (self.column / 8 + 1) * 8,
+                _ => self.column + ch.width().unwrap_or(0),
+            };
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<W: WriteColor> WriteColor for LineTracker<W> {
+    fn supports_color(&self) -> bool {
+        self.inner.supports_color()
+    }
+
+    fn set_color(&mut self, spec: &ColorSpec) -> io::Result<()> {
+        self.color = Some(spec.clone());
+        match &mut self.captured {
+            Some(captured) => {
+                captured.push(Captured::Color(spec.clone()));
+                Ok(())
+            }
+            None if !self.trailing.is_empty() || self.blank_lines > 0 => {
+                self.trailing.push(Trailing::Color(spec.clone()));
+                Ok(())
+            }
+            None => self.inner.set_color(spec),
+        }
+    }
+
+    fn reset(&mut self) -> io::Result<()> {
+        self.color = None;
+        match &mut self.captured {
+            Some(captured) => {
+                captured.push(Captured::Reset);
+                Ok(())
+            }
+            None if !self.trailing.is_empty() || self.blank_lines > 0 => {
+                self.trailing.push(Trailing::Reset);
+                Ok(())
+            }
+            None => self.inner.reset(),
+        }
+    }
+
+    fn supports_hyperlinks(&self) -> bool {
+        self.inner.supports_hyperlinks()
+    }
+
+    fn set_hyperlink(&mut self, link: &HyperlinkSpec) -> io::Result<()> {
+        self.take_blank_lines();
+        self.release_trailing()?;
+        self.inner.set_hyperlink(link)
+    }
+}
+
+/// Output stream wrapper that replaces every tab with a dimmed `→   ` marker,
+/// restoring the color that was active before it.
+pub struct ShowTabs<W> {
+    inner: W,
+    color: Option<ColorSpec>,
+}
+
+impl<W> ShowTabs<W> {
+    pub fn new(inner: W) -> Self {
+        ShowTabs { inner, color: None }
+    }
+}
+
+impl<W: WriteColor> Write for ShowTabs<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut chunks = buf.split(|&b| b == b'\t');
+        if let Some(first) = chunks.next() {
+            self.inner.write_all(first)?;
+        }
+        for chunk in chunks {
+            let mut marker = ColorSpec::new();
+            marker.set_dimmed(true);
+            self.inner.set_color(&marker)?;
+            self.inner.write_all("→   ".as_bytes())?;
+            self.inner.reset()?;
+            if let Some(color) = &self.color {
+                self.inner.set_color(color)?;
+            }
+            self.inner.write_all(chunk)?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<W: WriteColor> WriteColor for ShowTabs<W> {
+    fn supports_color(&self) -> bool {
+        self.inner.supports_color()
+    }
+
+    fn set_color(&mut self, spec: &ColorSpec) -> io::Result<()> {
+        self.color = Some(spec.clone());
+        self.inner.set_color(spec)
+    }
+
+    fn reset(&mut self) -> io::Result<()> {
+        self.color = None;
+        self.inner.reset()
+    }
+
+    fn supports_hyperlinks(&self) -> bool {
+        self.inner.supports_hyperlinks()
+    }
+
+    fn set_hyperlink(&mut self, link: &HyperlinkSpec) -> io::Result<()> {
+        self.inner.set_hyperlink(link)
+    }
+}
+
+/// Output stream wrapper that also keeps an uncolored copy of everything
+/// written, for `--clipboard`.
+#[cfg(feature = "clipboard")]
+pub struct CopyTo<'a, W> {
+    inner: W,
+    copy: &'a mut Vec<u8>,
+}
+
+#[cfg(feature = "clipboard")]
+impl<'a, W> CopyTo<'a, W> {
+    pub fn new(inner: W, copy: &'a mut Vec<u8>) -> Self {
+        CopyTo { inner, copy }
+    }
+}
+
+#[cfg(feature = "clipboard")]
+impl<W: Write> Write for CopyTo<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write_all(buf)?;
+        self.copy.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(feature = "clipboard")]
+impl<W: WriteColor> WriteColor for CopyTo<'_, W> {
+    fn supports_color(&self) -> bool {
+        self.inner.supports_color()
+    }
+
+    fn set_color(&mut self, spec: &ColorSpec) -> io::Result<()> {
+        self.inner.set_color(spec)
+    }
+
+    fn reset(&mut self) -> io::Result<()> {
+        self.inner.reset()
+    }
+
+    fn supports_hyperlinks(&self) -> bool {
+        self.inner.supports_hyperlinks()
+    }
+
+    fn set_hyperlink(&mut self, link: &HyperlinkSpec) -> io::Result<()> {
+        self.inner.set_hyperlink(link)
+    }
+}
+
+/// Output stream wrapper for `--preview-window-safe`: colors still go
+/// through, but hyperlinks are dropped and control characters in the text
+/// itself (such as a stray ESC in the source) are removed, so nothing but
+/// SGR color escapes reaches the host.
+pub struct PreviewSafe<W> {
+    inner: W,
+}
+
+impl<W> PreviewSafe<W> {
+    pub fn new(inner: W) -> Self {
+        PreviewSafe { inner }
+    }
+}
+
+impl<W: Write> Write for PreviewSafe<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for chunk in buf.split_inclusive(|&b| b.is_ascii_control() && !matches!(b, b'\n' | b'\t')) {
+            match chunk.split_last() {
+                Some((&last, rest)) if last.is_ascii_control() && !matches!(last, b'\n' | b'\t') => self.inner.write_all(rest)?,
+                _ => self.inner.write_all(chunk)?,
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<W: WriteColor> WriteColor for PreviewSafe<W> {
+    fn supports_color(&self) -> bool {
+        self.inner.supports_color()
+    }
+
+    fn set_color(&mut self, spec: &ColorSpec) -> io::Result<()> {
+        self.inner.set_color(spec)
+    }
+
+    fn reset(&mut self) -> io::Result<()> {
+        self.inner.reset()
+    }
+
+    fn supports_hyperlinks(&self) -> bool {
+        false
+    }
+
+    fn set_hyperlink(&mut self, _link: &HyperlinkSpec) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Byte stream wrapper for `--show-escapes`: the escape sequences written
+/// through it are shown as text, ESC as `\e` and other control characters
+/// in caret notation (`^G`), instead of reaching the terminal.
+pub struct ShowEscapes<W> {
+    inner: W,
+}
+
+impl<W> ShowEscapes<W> {
+    pub fn new(inner: W) -> Self {
+        ShowEscapes { inner }
+    }
+}
+
+impl<W: Write> Write for ShowEscapes<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for chunk in buf.split_inclusive(|&b| b.is_ascii_control() && !matches!(b, b'\n' | b'\t')) {
+            match chunk.split_last() {
+                Some((&last, rest)) if last.is_ascii_control() && !matches!(last, b'\n' | b'\t') => {
+                    self.inner.write_all(rest)?;
+                    match last {
+                        0x1b => self.inner.write_all(b"\\e")?,
+                        _ => self.inner.write_all(&[b'^', last ^ 0x40])?,
+                    }
+                }
+                _ => self.inner.write_all(chunk)?,
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Output stream wrapper for `--limit-bytes`: once the text written reaches
+/// the limit, the rest is dropped, a notice is written in its place and
+/// `exhausted` is set so the renderer can stop.
+pub struct LimitBytes<W> {
+    inner: W,
+    remaining: usize,
+    at_line_start: bool,
+    colored: bool,
+    link_open: bool,
+    exhausted: Rc<Cell<bool>>,
+}
+
+impl<W> LimitBytes<W> {
+    pub fn new(inner: W, limit: usize, exhausted: Rc<Cell<bool>>) -> Self {
+        LimitBytes { inner, remaining: limit, at_line_start: true, colored: false, link_open: false, exhausted }
+    }
+}
+
+impl<W: WriteColor> Write for LimitBytes<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.exhausted.get() {
+            return Ok(buf.len());
+        }
+        // What fits, cut back so no character is split
+        let mut fits = buf.len().min(self.remaining);
+        if fits < buf.len() {
+            while fits > 0 && (buf[fits] & 0xC0) == 0x80 {
+                fits -= 1;
+            }
+        }
+        if fits > 0 {
+            self.inner.write_all(&buf[..fits])?;
+            self.remaining -= fits;
+            self.at_line_start = buf[fits - 1] == b'\n';
+        }
+        if fits == buf.len() {
+            return Ok(buf.len());
+        }
+        self.exhausted.set(true);
+        if self.colored {
+            self.inner.reset()?;
+        }
+        if self.link_open {
+            self.inner.set_hyperlink(&HyperlinkSpec::close())?;
+        }
+        if !self.at_line_start {
+            writeln!(self.inner)?;
+        }
+        writeln!(self.inner, "… (output truncated)")?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<W: WriteColor> WriteColor for LimitBytes<W> {
+    fn supports_color(&self) -> bool {
+        self.inner.supports_color()
+    }
+
+    fn set_color(&mut self, spec: &ColorSpec) -> io::Result<()> {
+        if self.exhausted.get() {
+            return Ok(());
+        }
+        self.colored = true;
+        self.inner.set_color(spec)
+    }
+
+    fn reset(&mut self) -> io::Result<()> {
+        self.colored = false;
+        self.inner.reset()
+    }
+
+    fn supports_hyperlinks(&self) -> bool {
+        self.inner.supports_hyperlinks()
+    }
+
+    fn set_hyperlink(&mut self, link: &HyperlinkSpec) -> io::Result<()> {
+        if self.exhausted.get() {
+            return Ok(());
+        }
+        self.link_open = link.uri().is_some();
+        self.inner.set_hyperlink(link)
+    }
+}
+
+/// Output stream wrapper that resets the colors when dropped if a color is
+/// still set, so an error partway through a styled span can't leave the
+/// terminal colored.
+pub struct ResetOnDrop<W: WriteColor> {
+    inner: W,
+    colored: bool,
+}
+
+impl<W: WriteColor> ResetOnDrop<W> {
+    pub fn new(inner: W) -> Self {
+        ResetOnDrop { inner, colored: false }
+    }
+}
+
+impl<W: WriteColor> Drop for ResetOnDrop<W> {
+    fn drop(&mut self) {
+        if self.colored {
+            // Nothing more can be done if the stream is already gone
+            let _ = self.inner.reset();
+            let _ = self.inner.flush();
+        }
+    }
+}
+
+impl<W: WriteColor> Write for ResetOnDrop<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<W: WriteColor> WriteColor for ResetOnDrop<W> {
+    fn supports_color(&self) -> bool {
+        self.inner.supports_color()
+    }
+
+    fn set_color(&mut self, spec: &ColorSpec) -> io::Result<()> {
+        self.colored = !spec.is_none();
+        self.inner.set_color(spec)
+    }
+
+    fn reset(&mut self) -> io::Result<()> {
+        self.colored = false;
+        self.inner.reset()
+    }
+
+    fn supports_hyperlinks(&self) -> bool {
+        self.inner.supports_hyperlinks()
+    }
+
+    fn set_hyperlink(&mut self, link: &HyperlinkSpec) -> io::Result<()> {
+        self.inner.set_hyperlink(link)
+    }
+}
@@ -0,0 +1,93 @@
+//! `--interactive`: page through the rendered output with the arrow keys.
+
+use std::io::{self, Write};
+
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::terminal::{
+    self, Clear, ClearType, DisableLineWrap, EnableLineWrap, EnterAlternateScreen, LeaveAlternateScreen,
+};
+use crossterm::{execute, queue};
+
+use crate::term;
+
+/// Split rendered output into lines, starting each with the color escapes
+/// still active from the lines before so any window can be painted alone.
+fn split_lines(output: &str) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut active = String::new();
+    for line in output.lines() {
+        lines.push(format!("{}{}", active, line));
+        let mut rest = line;
+        while let Some(start) = rest.find("\x1b[") {
+            let Some(len) = rest[start..].find('m') else { break };
+            let escape = &rest[start..start + len + 1];
+            if escape == "\x1b[0m" {
+                active.clear();
+            } else {
+                active.push_str(escape);
+            }
+            rest = &rest[start + len + 1..];
+        }
+    }
+    lines
+}
+
+/// Puts the terminal back the way it was, however the pager exits.
+struct RestoreTerminal;
+
+impl Drop for RestoreTerminal {
+    fn drop(&mut self) {
+        let _ = execute!(io::stdout(), EnableLineWrap, Show, LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+fn paint(stdout: &mut impl Write, lines: &[String], top: usize, rows: usize) -> io::Result<()> {
+    let page = rows.saturating_sub(1); // The last row is the status line
+    for row in 0..page {
+        queue!(stdout, MoveTo(0, row as u16), Clear(ClearType::CurrentLine))?;
+        if let Some(line) = lines.get(top + row) {
+            write!(stdout, "{}\x1b[0m", line)?;
+        }
+    }
+    let last = (top + page).min(lines.len());
+    queue!(stdout, MoveTo(0, page as u16), Clear(ClearType::CurrentLine))?;
+    write!(stdout, "\x1b[2mlines {}-{} of {} (q to quit)\x1b[0m", top + 1, last, lines.len())?;
+    stdout.flush()
+}
+
+/// Show `output` full screen and scroll it with Up/Down, PageUp/PageDown,
+/// Home/End (or j/k, space/b, g/G) until `q`, Esc or Ctrl-C.
+pub fn page(output: &[u8]) -> io::Result<()> {
+    let lines = split_lines(&String::from_utf8_lossy(output));
+    let mut stdout = io::stdout();
+    terminal::enable_raw_mode()?;
+    let _restore = RestoreTerminal;
+    execute!(stdout, EnterAlternateScreen, Hide, DisableLineWrap)?;
+
+    let mut rows = term::height();
+    let mut top = 0;
+    loop {
+        let page = rows.saturating_sub(1).max(1);
+        top = top.min(lines.len().saturating_sub(page));
+        paint(&mut stdout, &lines, top, rows)?;
+
+        match event::read()? {
+            Event::Key(key) if key.kind != KeyEventKind::Release => match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => break,
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => break,
+                KeyCode::Up | KeyCode::Char('k') => top = top.saturating_sub(1),
+                KeyCode::Down | KeyCode::Char('j') | KeyCode::Enter => top += 1,
+                KeyCode::PageUp | KeyCode::Char('b') => top = top.saturating_sub(page),
+                KeyCode::PageDown | KeyCode::Char(' ') => top += page,
+                KeyCode::Home | KeyCode::Char('g') => top = 0,
+                KeyCode::End | KeyCode::Char('G') => top = lines.len(),
+                _ => {}
+            },
+            Event::Resize(_, height) => rows = height as usize,
+            _ => {}
+        }
+    }
+    Ok(())
+}
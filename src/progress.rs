@@ -0,0 +1,37 @@
+//! `--progress`: how far along each task list is.
+
+use pulldown_cmark::{Event, Tag, TagEnd};
+
+/// For each list in the document, in the order they start, how many of its
+/// own items are checked and how many are tasks at all, or `None` if it has
+/// no tasks. Tasks in nested lists count toward those lists only.
+pub fn counts(events: &[Event]) -> Vec<Option<(usize, usize)>> {
+    let mut counts = Vec::new();
+    let mut open = Vec::new(); // Indices into `counts` of the lists open around the current event
+    for event in events {
+        match event {
+            Event::Start(Tag::List(_)) => {
+                open.push(counts.len());
+                counts.push(None);
+            }
+            Event::End(TagEnd::List(_)) => {
+                open.pop();
+            }
+            Event::TaskListMarker(checked) => {
+                if let Some(&list) = open.last() {
+                    let (done, total) = counts[list].get_or_insert((0, 0));
+                    *done += usize::from(*checked);
+                    *total += 1;
+                }
+            }
+            _ => {}
+        }
+    }
+    counts
+}
+
+/// A bar like `[████░░░░░░]` split into the done and remaining parts.
+pub fn bar(done: usize, total: usize, width: usize) -> (String, String) {
+    let filled = (done * width + total / 2) / total.max(1);
+    ("█".repeat(filled), "░".repeat(width - filled))
+}
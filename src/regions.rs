@@ -0,0 +1,65 @@
+//! `--label-regions`: where a document's front matter, body and footer begin.
+
+use pulldown_cmark::{Event, Tag, TagEnd};
+
+/// A part of the document announced by a labelled separator.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Region {
+    Frontmatter,
+    Body,
+    /// Whatever follows the last top-level rule, when no heading does
+    Footer,
+}
+
+impl Region {
+    pub fn label(self) -> &'static str {
+        match self {
+            Region::Frontmatter => "frontmatter",
+            Region::Body => "body",
+            Region::Footer => "footer",
+        }
+    }
+}
+
+/// The event index each region starts at, in order. A footer starts at the
+/// rule that introduces it. Nothing is labelled when the body is all there is
+/// to see, including when the front matter is hidden and there's no footer.
+pub fn boundaries(events: &[Event], frontmatter_shown: bool) -> Vec<(usize, Region)> {
+    let mut depth = 0;
+    let mut metadata = None; // Start and end of the front matter block
+    let mut footer = None;
+    for (i, event) in events.iter().enumerate() {
+        match event {
+            Event::Start(Tag::MetadataBlock(_)) => metadata = Some((i, i)),
+            Event::End(TagEnd::MetadataBlock(_)) => {
+                if let Some((_, end)) = &mut metadata {
+                    *end = i;
+                }
+            }
+            Event::Start(Tag::Heading { .. }) => footer = None,
+            // A rule with nothing before it has no body to close
+            Event::Rule if depth == 0 && i > metadata.map_or(0, |(_, end)| end + 1) => footer = Some(i),
+            _ => {}
+        }
+        match event {
+            Event::Start(_) => depth += 1,
+            Event::End(_) => depth -= 1,
+            _ => {}
+        }
+    }
+    footer = footer.filter(|&at| at + 1 < events.len());
+
+    let frontmatter = metadata.filter(|_| frontmatter_shown);
+    if frontmatter.is_none() && footer.is_none() {
+        return Vec::new();
+    }
+    let mut boundaries = Vec::new();
+    if let Some((start, _)) = frontmatter {
+        boundaries.push((start, Region::Frontmatter));
+    }
+    boundaries.push((metadata.map_or(0, |(_, end)| end + 1), Region::Body));
+    if let Some(at) = footer {
+        boundaries.push((at, Region::Footer));
+    }
+    boundaries
+}
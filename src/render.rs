@@ -0,0 +1,1596 @@
+//! Terminal rendering: the `Renderer` holds all state needed to turn a stream
+//! of pulldown-cmark events into colored terminal output.
+
+use std::collections::HashSet;
+use std::io::{self, Write};
+use std::str::FromStr;
+
+use pulldown_cmark::{Alignment, BlockQuoteKind, CodeBlockKind, Event, HeadingLevel, Tag, TagEnd};
+use termcolor::{ColorSpec, HyperlinkSpec, WriteColor};
+use unicode_width::UnicodeWidthStr;
+
+use crate::{Args, CompletedTasks, MermaidMode, Spacing};
+use crate::details;
+use crate::frontmatter;
+use crate::glossary;
+use crate::math;
+use crate::progress;
+use crate::regions::Region;
+use crate::script::Script;
+#[cfg(feature = "inline-images")]
+use crate::images::Protocol;
+use crate::highlight::{self, CommentSyntax, Highlighter, Kind, Language};
+use crate::output::LineTracker;
+use crate::table::{Cell, Row, table_width, write_csv, write_expanded, write_table};
+use crate::term;
+use crate::theme::{HEADING_ICONS, Theme};
+use crate::validate;
+
+/// The `> ` markers (or `marker` and a space) for a blockquote nested `depth`
+/// levels deep, collapsing everything past `max_depth` into a single
+/// `>(depth) ` marker.
+fn quote_prefix(depth: usize, max_depth: Option<usize>, marker: char) -> String {
+    let level = format!("{} ", marker);
+    match max_depth {
+        Some(max) if depth > max => format!("{}{}({}) ", level.repeat(max.saturating_sub(1)), marker, depth),
+        _ => level.repeat(depth),
+    }
+}
+
+/// The header a callout of `kind` is shown with.
+fn callout_label(kind: BlockQuoteKind) -> &'static str {
+    match kind {
+        BlockQuoteKind::Note => "Note",
+        BlockQuoteKind::Tip => "Tip",
+        BlockQuoteKind::Important => "Important",
+        BlockQuoteKind::Warning => "Warning",
+        BlockQuoteKind::Caution => "Caution",
+    }
+}
+
+/// Expand tabs to spaces, padding each to the next stop every `tab_size`
+/// columns, for text starting at `column`.
+fn expand_tabs(text: &str, tab_size: usize, mut column: usize) -> String {
+    let mut expanded = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '\t' => {
+                let spaces = tab_size - column % tab_size;
+                expanded.push_str(&" ".repeat(spaces));
+                column += spaces;
+            }
+            '\n' => {
+                expanded.push(ch);
+                column = 0;
+            }
+            _ => {
+                expanded.push(ch);
+                column += 1;
+            }
+        }
+    }
+    expanded
+}
+
+/// How continuation lines of wrapped text are indented, as given to `--wrap-indent`.
+#[derive(Clone, Copy, Debug)]
+pub enum WrapIndent {
+    /// Line up with the text after a list item's marker; flush elsewhere.
+    Hang,
+    /// Start at the block's indentation, under a list item's marker.
+    Flush,
+    /// Indent this many spaces past the block's indentation.
+    Spaces(usize),
+}
+
+impl FromStr for WrapIndent {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "hang" => Ok(WrapIndent::Hang),
+            "flush" => Ok(WrapIndent::Flush),
+            _ => s.parse().map(WrapIndent::Spaces).map_err(|_| "expected hang, flush or a number of spaces".to_string()),
+        }
+    }
+}
+
+pub struct Renderer<'a, W: WriteColor> {
+    args: &'a Args,
+    theme: Theme,
+    stdout: LineTracker<W>,
+    /// Columns available for output, less the `--gutter`.
+    width: usize,
+    /// Whether each heading, in document order, was written setext-style.
+    setext_headings: Vec<bool>,
+    /// With `--detect-language`, the guessed language of each code block.
+    code_languages: Vec<Option<&'static str>>,
+    mermaid: Vec<Option<String>>, // Diagram summaries, one per code block, for --mermaid
+    abbreviations: Vec<(String, String)>, // With --abbr, those not spelled out yet
+    progress: Vec<Option<(usize, usize)>>, // Checked and total tasks of each list, for --progress
+    anchors: Option<HashSet<String>>, // Heading slugs `#slug` links are checked against, for --check-anchors
+    glossary: Vec<String>, // Defined terms to pick out where the prose uses them, for --glossary
+    list_index: usize,
+    hidden_lists: usize, // Nesting inside a task list replaced by its bar with --progress-only
+    hide_code: bool, // Inside a diagram replaced by its summary
+    highlight: Option<Highlighter>, // For the open code block, if its language is highlighted
+    degraded: Vec<(&'static str, usize)>, // Constructs not rendered faithfully, for --strict
+    after_heading: bool, // Nothing but a heading since the last block started
+    heading_attributes: Option<String>, // `{#id .class}` to show after the open heading
+
+    text_level: usize,
+    in_code_block: bool,
+    quote_depth: usize,
+    in_code: bool,
+    no_tab: bool,
+    in_list: bool,
+    lists: Vec<Option<u64>>, // Open lists, outermost first: the next number, or None for bullets
+    outer_levels: Vec<usize>, // Text level to restore when a nested list or definition closes
+    in_table: bool,
+    table_alignments: Vec<Alignment>,
+    table_rows: Vec<Row>,
+    current_row_cells: Vec<Cell>,
+    cell_colors: Vec<ColorSpec>, // Inline styles open inside the current cell
+    heading_index: usize,
+    setext_underline: Option<char>, // Underline glyph for the open setext heading
+    heading_width: usize,
+    heading_color: ColorSpec, // Color of the open heading
+    html_block: String, // Raw HTML chunks of the open HTML block
+    links: Vec<(String, String)>, // Destination and title of open links/images
+    link_open_pending: bool, // A link's `[` waits to see whether an image follows
+    linked_image: bool, // Rendering an image wrapped in a link as one unit
+    #[cfg(feature = "inline-images")]
+    image_protocol: Option<Protocol>, // How to draw images, with --inline-images in a terminal that can
+    #[cfg(feature = "inline-images")]
+    drawn_image: bool, // Inside an image drawn in place of its alt text
+    after_key: bool, // The last event closed a `<kbd>` key
+    script: Option<(Script, String)>, // Text of the open `<sup>` or `<sub>`
+    in_definition_title: bool, // The term of a definition list entry is open
+    details_depth: usize, // Open `<details>` sections shown expanded
+    collapsed_details: usize, // Nesting inside a `<details>` hidden by --collapse-details
+    code_lang: String,
+    code_block_index: usize,
+    marked_lines: Vec<(usize, usize)>, // Line ranges of the open code block picked out by --highlight-lines
+    code_line: usize, // Lines of the open code block written so far
+    code_lang_tag: Option<String>, // The language still to show at the end of the block's first line, for --show-code-lang
+    line_marked: bool, // The code line being written is one of `marked_lines`
+    code_buffer: String, // Content of a code block being folded
+    metadata: Option<String>, // YAML of the open front matter block
+    quote_line_start: bool, // Nothing written yet after the current quote line's markers
+    attribution: Option<String>, // A `— Author` quote line held back until the quote ends
+    attribution_closed: bool, // Its paragraph has ended too
+    completed_task: bool, // Inside a checked task list item
+    item_marker_widths: Vec<usize>, // Columns of each open list item's `- ` or `1. `, outermost first
+}
+
+impl<'a, W: WriteColor> Renderer<'a, W> {
+    #[allow(clippy::too_many_arguments)] // One per pre-pass over the events
+    pub fn new(
+        stdout: W,
+        args: &'a Args,
+        setext_headings: Vec<bool>,
+        code_languages: Vec<Option<&'static str>>,
+        mermaid: Vec<Option<String>>,
+        abbreviations: Vec<(String, String)>,
+        progress: Vec<Option<(usize, usize)>>,
+        anchors: Option<HashSet<String>>,
+        glossary: Vec<String>,
+    ) -> Self {
+        let mut theme = args.theme();
+        if args.no_code_color {
+            theme.code = ColorSpec::new();
+        }
+        Renderer {
+            args,
+            theme,
+            stdout: LineTracker::new(stdout),
+            width: term::width(args.width).saturating_sub(args.gutter),
+            setext_headings,
+            code_languages,
+            mermaid,
+            abbreviations,
+            progress,
+            anchors,
+            glossary,
+            list_index: 0,
+            hidden_lists: 0,
+            hide_code: false,
+            highlight: None,
+            degraded: Vec::new(),
+            after_heading: false,
+            heading_attributes: None,
+            text_level: 0,
+            in_code_block: false,
+            quote_depth: 0,
+            in_code: false,
+            no_tab: false,
+            in_list: false,
+            lists: Vec::new(),
+            outer_levels: Vec::new(),
+            in_table: false,
+            table_alignments: Vec::new(),
+            table_rows: Vec::new(),
+            current_row_cells: Vec::new(),
+            cell_colors: Vec::new(),
+            heading_index: 0,
+            setext_underline: None,
+            heading_width: 0,
+            heading_color: ColorSpec::new(),
+            html_block: String::new(),
+            links: Vec::new(),
+            link_open_pending: false,
+            linked_image: false,
+            #[cfg(feature = "inline-images")]
+            image_protocol: if args.inline_images { Protocol::detect() } else { None },
+            #[cfg(feature = "inline-images")]
+            drawn_image: false,
+            after_key: false,
+            script: None,
+            in_definition_title: false,
+            details_depth: 0,
+            collapsed_details: 0,
+            code_lang: String::new(),
+            code_block_index: 0,
+            marked_lines: Vec::new(),
+            code_line: 0,
+            code_lang_tag: None,
+            line_marked: false,
+            code_buffer: String::new(),
+            metadata: None,
+            quote_line_start: false,
+            attribution: None,
+            attribution_closed: false,
+            completed_task: false,
+            item_marker_widths: Vec::new(),
+        }
+    }
+
+    /// Draw the local image at `dest_url`, relative to `--base-path`, on a
+    /// line of its own. Returns whether it was drawn; remote images, images
+    /// in tables and files that are missing or can't be decoded are not.
+    #[cfg(feature = "inline-images")]
+    fn draw_image(&mut self, dest_url: &str) -> io::Result<bool> {
+        let Some(protocol) = self.image_protocol.filter(|_| !self.in_table && self.stdout.supports_color()) else {
+            return Ok(false);
+        };
+        if dest_url.contains("://") || dest_url.starts_with("data:") {
+            return Ok(false);
+        }
+        let escape = match protocol.draw(&self.args.base_path().join(dest_url), self.width) {
+            Ok(escape) => escape,
+            Err(err) => {
+                if self.args.verbose {
+                    eprintln!("{}: {}, showing its alt text", dest_url, err);
+                }
+                return Ok(false);
+            }
+        };
+        if !self.stdout.at_line_start() {
+            writeln!(self.stdout)?;
+        }
+        write!(self.stdout, "{}", escape)?;
+        writeln!(self.stdout)?;
+        Ok(true)
+    }
+
+    /// Note that a `what` was left out or simplified, for `--strict`.
+    fn degrade(&mut self, what: &'static str) {
+        match self.degraded.iter_mut().find(|(seen, _)| *seen == what) {
+            Some((_, count)) => *count += 1,
+            None => self.degraded.push((what, 1)),
+        }
+    }
+
+    /// The constructs that were not rendered faithfully so far, in the order
+    /// first seen, with how often each occurred.
+    pub fn degraded(&self) -> &[(&'static str, usize)] {
+        &self.degraded
+    }
+
+    /// Render a single event.
+    pub fn handle(&mut self, event: Event) -> io::Result<()> {
+        if self.args.tables_as_csv && !self.in_table && !matches!(event, Event::Start(Tag::Table(_))) {
+            return Ok(()); // Only tables are exported
+        }
+        if self.hidden_lists > 0 {
+            match event {
+                Event::Start(Tag::List(_)) => self.hidden_lists += 1,
+                Event::End(TagEnd::List(_)) => {
+                    self.hidden_lists -= 1;
+                    if self.hidden_lists == 0 && self.lists.is_empty() {
+                        self.block_break(1)?; // As after a top-level list
+                    }
+                }
+                _ => {}
+            }
+            return Ok(());
+        }
+        if self.collapsed_details > 0
+            && !matches!(event, Event::Start(Tag::HtmlBlock) | Event::Html(_) | Event::End(TagEnd::HtmlBlock))
+        {
+            return Ok(()); // Only the HTML that may close the section matters
+        }
+        #[cfg(feature = "inline-images")]
+        if self.drawn_image {
+            self.drawn_image = !matches!(event, Event::End(TagEnd::Image));
+            return Ok(()); // The alt text isn't needed
+        }
+        if self.attribution.is_some() && self.hold_attribution(&event)? {
+            return Ok(());
+        }
+        let quote_line_start = std::mem::take(&mut self.quote_line_start);
+        if self.args.quote_attribution
+            && quote_line_start
+            && let Event::Text(text) = &event
+            && (text.starts_with('—') || text.starts_with("--") || text.starts_with("- "))
+        {
+            self.attribution = Some(text.to_string());
+            return Ok(());
+        }
+        if matches!(event, Event::Start(Tag::Paragraph)) {
+            self.quote_line_start = quote_line_start;
+        }
+        if self.link_open_pending {
+            self.link_open_pending = false;
+            if matches!(event, Event::Start(Tag::Image { .. })) {
+                self.linked_image = true; // Badge-style `[![alt](img)](url)`
+            } else {
+                self.write_inline(self.args.link_brackets.delimiters().0)?;
+            }
+        }
+        let after_key = std::mem::take(&mut self.after_key);
+        match event {
+            Event::Start(tag) => self.start(tag)?,
+            Event::End(tag_end) => self.end(tag_end)?,
+            Event::Text(text) if self.script.is_some() => {
+                if let Some((_, raised)) = &mut self.script {
+                    raised.push_str(&text);
+                }
+            },
+            Event::Text(text) if !self.glossary.is_empty() && !self.in_definition_title && !self.in_code_block && self.metadata.is_none() => {
+                self.glossary_text(&text)?
+            },
+            Event::Text(text) => self.prose(&text)?,
+            Event::Code(code) => self.code(&code)?,
+            Event::SoftBreak => self.soft_break()?,
+            Event::HardBreak => self.hard_break()?,
+            Event::Rule => self.rule()?,
+            Event::Html(html) => self.html_block.push_str(&html),
+            Event::InlineHtml(html) if self.args.show_comments && html.starts_with("<!--") => {
+                self.stdout.set_color(&self.theme.comment)?;
+                write!(self.stdout, "{}", html)?;
+                self.stdout.reset()?;
+            },
+            Event::InlineHtml(html) if html.eq_ignore_ascii_case("<sup>") => self.script = Some((Script::Super, String::new())),
+            Event::InlineHtml(html) if html.eq_ignore_ascii_case("<sub>") => self.script = Some((Script::Sub, String::new())),
+            Event::InlineHtml(html) if html.eq_ignore_ascii_case("</sup>") || html.eq_ignore_ascii_case("</sub>") => {
+                if let Some((script, text)) = self.script.take() {
+                    self.text(&script.render(&text))?;
+                }
+            },
+            Event::InlineHtml(html) if html.eq_ignore_ascii_case("<kbd>") => {
+                if after_key {
+                    self.write_inline("+")?; // `<kbd>Ctrl</kbd><kbd>C</kbd>` reads as Ctrl+C
+                }
+                self.start_key()?;
+            },
+            Event::InlineHtml(html) if html.eq_ignore_ascii_case("</kbd>") => {
+                self.end_key()?;
+                self.after_key = true;
+            },
+            Event::InlineHtml(html) => {
+                if !html.starts_with("<!--") {
+                    self.degrade("inline HTML");
+                }
+            },
+            Event::InlineMath(math) | Event::DisplayMath(math) if self.args.math_unicode => {
+                let converted = math::to_unicode(&math);
+                if converted.contains(['\\', '^', '_', '{']) {
+                    self.degrade("math"); // Only partly converted
+                }
+                self.text(&converted)?;
+            },
+            Event::InlineMath(math) => {
+                self.degrade("math");
+                self.text(&format!("${}$", math))?;
+            },
+            Event::DisplayMath(math) => {
+                self.degrade("math");
+                self.text(&format!("$${}$$", math))?;
+            },
+            // Only numbered footnotes are raised; `[^note]` stays readable
+            Event::FootnoteReference(name) => match Script::Super.glyphs(&name).filter(|_| name.chars().all(|c| c.is_ascii_digit())) {
+                Some(marker) if self.args.footnote_superscript => write!(self.stdout, "{}", marker)?,
+                _ => write!(self.stdout, "[^{}]", name)?,
+            },
+            Event::TaskListMarker(checked) => {
+                let (open, done) = self.args.checkbox_style.markers();
+                write!(self.stdout, "{} ", if checked { done } else { open })?;
+                self.completed_task = checked;
+            },
+        }
+        self.stdout.flush()
+    }
+
+    /// With a possible attribution line held back, decide what `event` means
+    /// for it: more of the line, its paragraph ending, the quote ending (so it
+    /// is an attribution), or anything else (so it was ordinary text after all).
+    /// Returns whether the event was used up.
+    fn hold_attribution(&mut self, event: &Event) -> io::Result<bool> {
+        match event {
+            Event::Text(text) if !self.attribution_closed => {
+                if let Some(line) = &mut self.attribution {
+                    line.push_str(text);
+                }
+                return Ok(true);
+            }
+            Event::End(TagEnd::Paragraph) if !self.attribution_closed => {
+                self.attribution_closed = true;
+                return Ok(true);
+            }
+            _ => {}
+        }
+        let line = self.attribution.take().unwrap_or_default();
+        let closed = std::mem::take(&mut self.attribution_closed);
+        if closed && matches!(event, Event::End(TagEnd::BlockQuote(_))) {
+            // Right-align the attribution after the quote markers already written
+            let markers = self.indent_width() + quote_prefix(self.quote_depth, self.args.max_blockquote_depth, self.args.quote_marker()).width();
+            let padding = self.width.saturating_sub(markers + term::text_width(&line));
+            write!(self.stdout, "{}", " ".repeat(padding))?;
+            self.stdout.set_color(&self.theme.attribution)?;
+            write!(self.stdout, "{}", line)?;
+            self.stdout.reset()?;
+            writeln!(self.stdout)?;
+        } else {
+            self.text(&line)?;
+            if closed {
+                self.end(TagEnd::Paragraph)?;
+            }
+        }
+        Ok(false)
+    }
+
+    /// Reset colors one last time and end the output with a single newline,
+    /// leaving off the blank lines after the last block.
+    pub fn finish(&mut self) -> io::Result<()> {
+        self.stdout.end(!self.args.no_trailing_newline)?;
+        self.stdout.flush()
+    }
+
+    /// End the current block and leave `blank_lines` blank lines before the
+    /// next one at normal `--spacing`: one fewer when tight and one more when
+    /// loose. Breaks that meet between two blocks share the larger count, so
+    /// the spacing is adjusted once per gap.
+    fn block_break(&mut self, mut blank_lines: usize) -> io::Result<()> {
+        if !self.stdout.at_line_start() {
+            // A block opening after a list marker or quote prefix counts that line
+            writeln!(self.stdout)?;
+            blank_lines = blank_lines.saturating_sub(1);
+        }
+        self.stdout.blank_lines(match self.args.spacing {
+            Spacing::Tight => blank_lines.saturating_sub(1),
+            Spacing::Normal => blank_lines,
+            Spacing::Loose => blank_lines + 1,
+        });
+        Ok(())
+    }
+
+    /// Write the indentation for the current level: tabs by default (two
+    /// spaces with `--two-space-indent`), or the `--indent-char` guide glyph
+    /// drawn dimmed at each level.
+    fn write_indent(&mut self) -> io::Result<()> {
+        match self.args.indent_char {
+            None => write!(self.stdout, "{}", self.args.indent_unit().repeat(self.text_level)),
+            Some(glyph) => {
+                self.stdout.set_color(&self.theme.indent)?;
+                for _ in 0..self.text_level {
+                    write!(self.stdout, "{} ", glyph)?;
+                }
+                self.stdout.reset()
+            }
+        }
+    }
+
+    /// Columns taken up by the indentation for the current level.
+    fn indent_width(&self) -> usize {
+        // Tabs advance to the next multiple of 8; guides are a glyph and a space
+        let per_level = if self.args.indent_char.is_some() || self.args.two_space_indent { 2 } else { 8 };
+        self.text_level * per_level
+    }
+
+    /// The start of every line of a table nested in a list item or blockquote:
+    /// the indentation and quote markers, plus room for the item's marker.
+    /// Tables outside lists and quotes stay flush left.
+    fn table_prefix(&self) -> Cell {
+        let mut prefix = Cell::default();
+        if self.lists.is_empty() && self.quote_depth == 0 {
+            return prefix;
+        }
+        match self.args.indent_char {
+            None => prefix.push(&self.args.indent_unit().repeat(self.text_level), None),
+            Some(glyph) => {
+                for _ in 0..self.text_level {
+                    prefix.push(&format!("{} ", glyph), Some(&self.theme.indent));
+                }
+            }
+        }
+        if self.quote_depth > 0 {
+            prefix.push(&self.quote_markers(), Some(&self.quote_color()));
+        }
+        if !self.lists.is_empty() {
+            prefix.push(&" ".repeat(self.item_marker_width()), None); // Line up with the item text after its marker
+        }
+        prefix
+    }
+
+    /// Columns a table after `prefix` may take up with `--max-table-width`.
+    fn max_table_width(&self, prefix: &Cell) -> Option<usize> {
+        let max = self.args.max_table_width?.unwrap_or(self.width);
+        Some(max.saturating_sub(prefix.width()))
+    }
+
+    /// The `> ` (or `--quote-bar`) markers for the current quote depth; none with `--no-blockquote-indent`.
+    fn quote_markers(&self) -> String {
+        if self.args.no_blockquote_indent {
+            return String::new();
+        }
+        quote_prefix(self.quote_depth, self.args.max_blockquote_depth, self.args.quote_marker())
+    }
+
+    /// The color of the current quote's markers, which `--nested-quote-colors`
+    /// varies by depth.
+    fn quote_color(&self) -> ColorSpec {
+        if self.args.nested_quote_colors {
+            self.theme.quote_level(self.quote_depth).clone()
+        } else {
+            self.theme.blockquote.clone()
+        }
+    }
+
+    /// Start a quoted line: indentation followed by the blockquote markers.
+    fn write_quote_prefix(&mut self) -> io::Result<()> {
+        self.write_indent()?;
+        self.stdout.set_color(&self.quote_color())?;
+        write!(self.stdout, "{}", self.quote_markers())?;
+        self.quote_line_start = true;
+        self.stdout.reset()
+    }
+
+    /// Append text to the current table cell in the innermost open inline style.
+    fn push_cell(&mut self, text: &str) {
+        let color = self.cell_colors.last().cloned();
+        self.push_cell_colored(text, color.as_ref());
+    }
+
+    /// Append text to the current table cell in `color`. Tabs are expanded
+    /// first, as the column widths count every character as one column.
+    fn push_cell_colored(&mut self, text: &str, color: Option<&ColorSpec>) {
+        if let Some(cell) = self.current_row_cells.last_mut() {
+            if text.contains('\t') {
+                cell.push(&expand_tabs(text, self.args.tab_size_for_tables, cell.width()), color);
+            } else {
+                cell.push(text, color);
+            }
+        }
+    }
+
+    /// Write inline text, into the current cell when inside a table.
+    fn write_inline(&mut self, text: &str) -> io::Result<()> {
+        if self.in_table {
+            self.push_cell(text);
+            Ok(())
+        } else {
+            if self.setext_underline.is_some() {
+                self.heading_width += term::text_width(text); // Delimiters count toward the underline
+            }
+            write!(self.stdout, "{}", text)
+        }
+    }
+
+    /// Open an inline style (strong, emphasis, strikethrough) and its marker.
+    fn start_inline(&mut self, color: ColorSpec, marker: &str) -> io::Result<()> {
+        if self.in_table {
+            self.cell_colors.push(color);
+            if self.args.symbol {
+                self.push_cell(marker);
+            }
+        } else {
+            self.no_tab = true;
+            self.stdout.set_color(&color)?;
+            if self.args.symbol {
+                self.write_inline(marker)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Close an inline style opened by `start_inline`.
+    fn end_inline(&mut self, marker: &str) -> io::Result<()> {
+        if self.in_table {
+            if self.args.symbol {
+                self.push_cell(marker);
+            }
+            self.cell_colors.pop();
+        } else {
+            if self.args.symbol {
+                self.write_inline(marker)?;
+            }
+            self.stdout.reset()?;
+        }
+        Ok(())
+    }
+
+    /// With `--check-anchors`, the color of a `#slug` link: whether a heading
+    /// has that slug or the link is broken.
+    fn anchor_color(&self, dest_url: &str) -> Option<ColorSpec> {
+        let anchor = dest_url.strip_prefix('#').filter(|anchor| !anchor.is_empty())?;
+        let slugs = self.anchors.as_ref()?;
+        Some(if slugs.contains(anchor) { self.theme.anchor.clone() } else { self.theme.broken_anchor.clone() })
+    }
+
+    /// Open a `<kbd>` key: a keycap in color, `[` without.
+    fn start_key(&mut self) -> io::Result<()> {
+        if self.stdout.supports_color() {
+            self.start_inline(self.theme.key.clone(), "")?;
+            self.write_inline(" ")
+        } else {
+            self.write_inline("[")
+        }
+    }
+
+    /// Close a key opened by `start_key`.
+    fn end_key(&mut self) -> io::Result<()> {
+        if self.stdout.supports_color() {
+            self.write_inline(" ")?;
+            self.end_inline("")
+        } else {
+            self.write_inline("]")
+        }
+    }
+
+    /// Render part of a `<details>` section: its summary as a `▸` header over
+    /// the indented content, or only the summary with `--collapse-details`.
+    fn details_part(&mut self, part: details::Part) -> io::Result<()> {
+        match part {
+            details::Part::Open(_) if self.collapsed_details > 0 => self.collapsed_details += 1,
+            details::Part::Open(summary) => {
+                self.block_break(1)?;
+                self.write_indent()?;
+                self.stdout.set_color(&self.theme.fence)?;
+                write!(self.stdout, "▸ ")?;
+                self.stdout.set_color(&self.theme.details_summary)?;
+                write!(self.stdout, "{}", summary.as_deref().unwrap_or("Details"))?;
+                self.stdout.reset()?;
+                writeln!(self.stdout)?;
+                if self.args.collapse_details {
+                    self.collapsed_details = 1;
+                } else {
+                    self.details_depth += 1;
+                    self.text_level += 1;
+                }
+            }
+            details::Part::Close if self.collapsed_details > 0 => self.collapsed_details -= 1,
+            details::Part::Close if self.details_depth > 0 => {
+                self.details_depth -= 1;
+                self.text_level = self.text_level.saturating_sub(1); // A heading inside may have reset it
+            }
+            details::Part::Close => {}
+            details::Part::Text(_) if self.collapsed_details > 0 => {}
+            details::Part::Text(line) => {
+                self.write_indent()?;
+                writeln!(self.stdout, "{}", line)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Write `[████░░░░░░] 4/10 done` above a task list, at its indentation.
+    fn progress_bar(&mut self, done: usize, total: usize) -> io::Result<()> {
+        if !self.stdout.at_line_start() {
+            writeln!(self.stdout)?; // A nested list starts right after its parent's text
+        }
+        self.write_indent()?;
+        let (filled, empty) = progress::bar(done, total, 10);
+        write!(self.stdout, "[")?;
+        self.stdout.set_color(&self.theme.progress)?;
+        write!(self.stdout, "{}", filled)?;
+        self.stdout.set_color(&self.theme.indent)?;
+        write!(self.stdout, "{}", empty)?;
+        self.stdout.reset()?;
+        writeln!(self.stdout, "] {}/{} done", done, total)
+    }
+
+    fn start(&mut self, tag: Tag) -> io::Result<()> {
+        self.stdout.reset()?;
+        let after_heading = std::mem::take(&mut self.after_heading);
+        match tag {
+            Tag::Paragraph if self.quote_depth > 0 && self.stdout.at_line_start() => {
+                // Later paragraphs of a quote start on a fresh line and need their markers
+                self.write_quote_prefix()?;
+            },
+            Tag::Paragraph if self.quote_depth == 0 && self.lists.is_empty() => {
+                if self.args.true_center {
+                    self.stdout.capture();
+                }
+                if let Some(spaces) = self.args.indent_first_line
+                    && !(after_heading && self.args.no_indent_after_heading)
+                {
+                    self.write_indent()?;
+                    write!(self.stdout, "{:1$}", "", spaces)?;
+                }
+            },
+            Tag::Paragraph if !self.lists.is_empty() && self.stdout.at_line_start() => {
+                // A later paragraph of the item lines up with the text after its marker
+                self.write_indent()?;
+                write!(self.stdout, "{:1$}", "", self.item_marker_width())?;
+                self.in_list = true; // The indentation is written
+            },
+            Tag::Paragraph => (),
+            Tag::HtmlBlock => {
+                // Raw HTML can follow a tight list item's text on the same line
+                self.html_block.clear();
+                self.block_break(0)?;
+            },
+            Tag::Heading { level, id, classes, attrs } => {
+                let mut attributes: Vec<String> = id.iter().map(|id| format!("#{}", id)).collect();
+                attributes.extend(classes.iter().map(|class| format!(".{}", class)));
+                attributes.extend(attrs.iter().map(|(key, value)| match value {
+                    Some(value) => format!("{}={}", key, value),
+                    None => key.to_string(),
+                }));
+                self.heading_attributes = Some(format!("{{{}}}", attributes.join(" ")))
+                    .filter(|_| self.args.show_heading_attributes && !attributes.is_empty());
+                self.no_tab = true;
+                self.text_level = level as usize - 1 + self.args.center;
+                let blank_lines = match level {
+                    _ if !self.args.progressive_spacing => 1,
+                    // The first heading of the output gets no more room than usual
+                    HeadingLevel::H1 if self.stdout.written() => 2,
+                    HeadingLevel::H1 | HeadingLevel::H2 => 1,
+                    _ => 0,
+                };
+                self.block_break(blank_lines)?;
+                let hash_prefix = "#".repeat(self.text_level + 1);
+                let setext = self.setext_headings.get(self.heading_index).copied().unwrap_or(false);
+                self.heading_index += 1;
+                self.heading_width = 0;
+                if self.args.true_center {
+                    self.stdout.capture();
+                }
+                self.write_indent()?;
+                self.heading_color = if self.args.heading_color_by_level {
+                    self.theme.heading_level(level as usize).clone()
+                } else {
+                    self.theme.heading.clone()
+                };
+                self.stdout.set_color(&self.heading_color)?;
+                if self.args.heading_icons {
+                    let icon = HEADING_ICONS[level as usize - 1];
+                    write!(self.stdout, "{} ", icon)?;
+                    self.heading_width += term::text_width(icon) + 1;
+                }
+                if setext {
+                    self.setext_underline = Some(self.args.heading_underline(level));
+                } else if self.args.symbol {
+                    write!(self.stdout, "{} ", hash_prefix)?;
+                }
+            },
+            Tag::Strong => self.start_inline(self.theme.strong.clone(), "**")?,
+            Tag::Emphasis => self.start_inline(self.theme.emphasis.clone(), "*")?,
+            Tag::Strikethrough => self.start_inline(self.theme.strikethrough.clone(), "~~")?,
+            Tag::BlockQuote(kind) => {
+                if self.quote_depth > 0 {
+                    // Separate a nested quote with a line still carrying the outer markers
+                    self.write_indent()?;
+                    self.stdout.set_color(&self.quote_color())?;
+                    writeln!(self.stdout, "{}", self.quote_markers().trim_end())?;
+                } else {
+                    self.block_break(1)?;
+                }
+                self.quote_depth += 1;
+                self.write_quote_prefix()?;
+                if let Some(kind) = kind {
+                    // A `> [!NOTE]` callout's marker line becomes its header
+                    self.stdout.set_color(self.theme.callout(kind))?;
+                    writeln!(self.stdout, "{} {}", self.args.callout_style.icon(kind), callout_label(kind))?;
+                    self.stdout.reset()?;
+                    self.write_quote_prefix()?;
+                }
+                self.quote_line_start = false; // A quote's first line is never its attribution
+            },
+            Tag::CodeBlock(kind) => {
+                self.in_code_block = true;
+                let mut lang_str = match kind {
+                    CodeBlockKind::Fenced(lang) => lang.to_string(),
+                    CodeBlockKind::Indented => String::new(),
+                };
+                let labeled = !lang_str.trim().is_empty();
+                if let Some(Some(guess)) = self.code_languages.get(self.code_block_index) {
+                    lang_str = guess.to_string();
+                }
+                let (info, marked_lines) = highlight::marked_lines(&lang_str);
+                self.marked_lines = if self.args.highlight_lines { marked_lines } else { Vec::new() };
+                self.code_line = 0;
+                self.code_lang_tag = info.split_whitespace().next().filter(|_| labeled && self.args.show_code_lang).map(str::to_string);
+                self.highlight = Language::from_info(info)
+                    .filter(|&language| language != Language::Shell || self.args.shell_prompts)
+                    .or_else(|| CommentSyntax::from_info(info).filter(|_| self.args.dim_comments).map(Language::Comments))
+                    .map(Highlighter::new);
+                let diagram = self.mermaid.get(self.code_block_index).cloned().flatten();
+                self.code_block_index += 1;
+                if let Some(summary) = diagram {
+                    self.write_indent()?;
+                    self.stdout.set_color(&self.theme.fence)?;
+                    writeln!(self.stdout, "[mermaid diagram: {}]", summary)?;
+                    self.stdout.reset()?;
+                    if self.args.mermaid == MermaidMode::Summarize {
+                        self.hide_code = true;
+                        return Ok(());
+                    }
+                }
+                if self.args.fold_code {
+                    self.code_lang = lang_str; // The block is summarized once it ends
+                } else if self.args.symbol && !self.args.no_symbol_fences {
+                    self.write_indent()?;
+                    self.stdout.set_color(&self.theme.fence)?; // Set fence color
+                    write!(self.stdout, "```")?;
+                    self.stdout.set_color(&self.theme.code)?; // Set code color for language
+                    write!(self.stdout, "{}", lang_str)?;
+                    writeln!(self.stdout)?; // Newline after language info
+                } else {
+                    self.stdout.set_color(&self.theme.code)?; // Set code color for language
+                }
+            },
+            Tag::List(start) => {
+                let nested = !self.lists.is_empty();
+                if nested {
+                    self.outer_levels.push(self.text_level);
+                    self.text_level += 1; // Nested lists sit one level deeper than their parent
+                }
+                let progress = self.progress.get(self.list_index).copied().flatten();
+                self.list_index += 1;
+                if let Some((done, total)) = progress.filter(|_| self.args.progress || self.args.progress_only) {
+                    self.progress_bar(done, total)?;
+                    if self.args.progress_only {
+                        self.text_level -= usize::from(nested);
+                        self.hidden_lists = 1;
+                        return Ok(());
+                    }
+                }
+                self.lists.push(start);
+            },
+            Tag::Item => {
+                if !self.stdout.at_line_start() {
+                    writeln!(self.stdout)?; // A nested list starts right after its parent's text
+                }
+                self.in_list = true;
+                self.write_indent()?;
+                // Ordered lists around this one, for --nested-ordered-styles
+                let depth = self.lists.iter().filter(|list| list.is_some()).count().saturating_sub(1);
+                let style = if self.args.nested_ordered_styles {
+                    self.args.ordered_style.nested(depth)
+                } else {
+                    self.args.ordered_style
+                };
+                let marker = match self.lists.last_mut() {
+                    Some(Some(number)) => {
+                        *number += 1;
+                        style.marker(*number - 1)
+                    }
+                    _ => "- ".to_string(),
+                };
+                self.item_marker_widths.push(marker.width());
+                write!(self.stdout, "{}", marker)?;
+            },
+            Tag::Link { dest_url, title, .. } => {
+                if let Some(color) = self.anchor_color(&dest_url) {
+                    self.start_inline(color, "")?;
+                }
+                self.links.push((dest_url.to_string(), title.to_string()));
+                self.link_open_pending = true; // Written by `handle` unless an image follows
+            },
+            Tag::Image { dest_url, title, .. } => {
+                #[cfg(feature = "inline-images")]
+                if !self.linked_image && self.draw_image(&dest_url)? {
+                    self.drawn_image = true;
+                    return Ok(());
+                }
+                self.links.push((dest_url.to_string(), title.to_string()));
+                if self.linked_image {
+                    self.write_inline("🖼 ")?;
+                } else {
+                    // Without brackets, an image is just its alt text
+                    let (open, _) = self.args.link_brackets.delimiters();
+                    if !open.is_empty() {
+                        self.write_inline(&format!("!{}", open))?;
+                    }
+                }
+            },
+            Tag::Table(alignments) => {
+                self.in_table = true;
+                self.table_alignments = alignments;
+                self.table_rows.clear(); // Clear previous table's rows
+                self.current_row_cells.clear(); // Clear any lingering cell data
+                if !self.args.tables_as_csv {
+                    self.block_break(1)?; // Newline before table
+                } else if self.stdout.written() {
+                    writeln!(self.stdout)?; // A blank line between exported tables
+                }
+            },
+            Tag::TableHead | Tag::TableRow => {
+                self.current_row_cells.clear(); // Start a new row, clear previous cells
+            },
+            Tag::TableCell => {
+                self.current_row_cells.push(Cell::default());
+            },
+            Tag::MetadataBlock(_) => self.metadata = Some(String::new()),
+            Tag::FootnoteDefinition(label) => {
+                // Definitions keep the full `[^name]` label, even with --footnote-superscript
+                self.block_break(0)?;
+                self.write_indent()?;
+                write!(self.stdout, "[^{}]: ", label)?;
+            },
+            Tag::DefinitionList => self.block_break(1)?,
+            Tag::DefinitionListTitle => {
+                if !self.stdout.at_line_start() {
+                    writeln!(self.stdout)?;
+                }
+                self.write_indent()?;
+                self.no_tab = true;
+                self.in_definition_title = true;
+                self.stdout.set_color(&self.theme.definition_term)?;
+            },
+            Tag::DefinitionListDefinition => {
+                if !self.stdout.at_line_start() {
+                    writeln!(self.stdout)?;
+                }
+                self.outer_levels.push(self.text_level);
+                self.text_level += 1;
+            },
+            Tag::Superscript => self.degrade("superscript"),
+            Tag::Subscript => self.degrade("subscript"),
+        }
+        Ok(())
+    }
+
+    fn end(&mut self, tag_end: TagEnd) -> io::Result<()> {
+        match tag_end {
+            TagEnd::DefinitionListTitle => {
+                self.stdout.reset()?;
+                self.in_definition_title = false;
+                self.no_tab = false;
+                writeln!(self.stdout)?;
+            },
+            TagEnd::DefinitionListDefinition => {
+                if !self.stdout.at_line_start() {
+                    writeln!(self.stdout)?;
+                }
+                self.text_level = self.outer_levels.pop().unwrap_or_default(); // A heading inside may have reset it
+            },
+            TagEnd::DefinitionList => self.block_break(1)?,
+            TagEnd::Paragraph => {
+                writeln!(self.stdout)?;
+                self.stdout.write_centered(self.width)?;
+                if self.quote_depth == 0 && self.lists.is_empty() {
+                    self.block_break(0)?;
+                }
+            },
+            TagEnd::Heading { .. } => {
+                self.after_heading = true;
+                if let Some(attributes) = self.heading_attributes.take() {
+                    self.stdout.set_color(&self.theme.heading_attributes)?;
+                    write!(self.stdout, " {}", attributes)?;
+                    self.heading_width += 1 + term::text_width(&attributes);
+                    self.stdout.set_color(&self.heading_color)?; // The setext underline keeps the heading color
+                }
+                writeln!(self.stdout)?; // Newline for the end of the heading
+                if let Some(underline) = self.setext_underline.take() {
+                    self.write_indent()?;
+                    self.stdout.set_color(&self.heading_color)?;
+                    // As many glyphs as fit under the heading, wide ones included
+                    let glyph_width = term::text_width(underline.encode_utf8(&mut [0; 4])).max(1);
+                    writeln!(self.stdout, "{}", underline.to_string().repeat(self.heading_width / glyph_width))?;
+                }
+                self.stdout.reset()?; // Reset color after the heading
+                self.stdout.write_centered(self.width)?;
+                self.no_tab = false;
+                self.block_break(0)?;
+            },
+            TagEnd::Strong => self.end_inline("**")?,
+            TagEnd::Emphasis => self.end_inline("*")?,
+            TagEnd::Strikethrough => self.end_inline("~~")?,
+            TagEnd::BlockQuote(_) => {
+                self.stdout.reset()?;
+                self.quote_depth -= 1;
+                if self.quote_depth == 0 {
+                    self.block_break(1)?;
+                }
+            },
+            TagEnd::CodeBlock if self.hide_code => {
+                self.hide_code = false;
+                self.in_code_block = false;
+            },
+            TagEnd::CodeBlock if self.args.fold_code => {
+                let lines = self.code_buffer.lines().count();
+                let plural = if lines == 1 { "" } else { "s" };
+                self.write_indent()?;
+                self.stdout.set_color(&self.theme.fence)?;
+                if self.code_lang.is_empty() {
+                    writeln!(self.stdout, "▸ code ({} line{})", lines, plural)?;
+                } else {
+                    writeln!(self.stdout, "▸ code ({}, {} line{})", self.code_lang, lines, plural)?;
+                }
+                self.stdout.reset()?;
+                self.code_buffer.clear();
+                self.in_code_block = false;
+            },
+            TagEnd::CodeBlock => {
+                self.write_indent()?;
+                self.stdout.set_color(&self.theme.fence)?;
+                if self.args.symbol && !self.args.no_symbol_fences {
+                    write!(self.stdout, "```")?;
+                }
+                writeln!(self.stdout)?;
+                self.in_code_block = false;
+            },
+            TagEnd::List(_) => {
+                self.lists.pop();
+                if self.lists.is_empty() {
+                    self.block_break(1)?;
+                } else {
+                    self.text_level = self.outer_levels.pop().unwrap_or_default(); // A heading inside may have reset it
+                }
+            },
+            TagEnd::Item => {
+                self.item_marker_widths.pop();
+                self.completed_task = false;
+                if !self.stdout.at_line_start() {
+                    writeln!(self.stdout)?; // Nested lists already ended their last line
+                }
+                self.in_list = false;
+            },
+            TagEnd::Image if self.linked_image => {
+                self.links.pop(); // The enclosing link's destination is shown instead
+            },
+            TagEnd::Link if self.linked_image => {
+                let (dest_url, _) = self.links.pop().unwrap_or_default();
+                self.linked_image = false;
+                self.write_inline(&format!(" → {}", dest_url))?;
+                if self.anchor_color(&dest_url).is_some() {
+                    self.end_inline("")?;
+                }
+            },
+            TagEnd::Link | TagEnd::Image => {
+                let (dest_url, title) = self.links.pop().unwrap_or_default();
+                let (_, close) = self.args.link_brackets.delimiters();
+                self.write_inline(close)?;
+                if tag_end == TagEnd::Link && self.anchor_color(&dest_url).is_some() {
+                    self.end_inline("")?;
+                }
+                if !self.args.show_urls {
+                    return Ok(());
+                }
+                // The destination follows in its own parentheses: `(url — "title")`
+                let separator = if close.is_empty() { " " } else { "" };
+                let url = format!("{}({}", separator, dest_url);
+                let title = if title.is_empty() { String::new() } else { format!(" — \"{}\"", title) };
+                if self.in_table {
+                    self.push_cell(&url);
+                    if let Some(cell) = self.current_row_cells.last_mut() {
+                        cell.push(&title, Some(&self.theme.link_title));
+                    }
+                    self.push_cell(")");
+                } else {
+                    self.write_inline(&url)?;
+                    self.stdout.set_color(&self.theme.link_title)?;
+                    self.write_inline(&title)?;
+                    self.stdout.reset()?;
+                    self.write_inline(")")?;
+                }
+            },
+            TagEnd::HtmlBlock if details::is_details(&self.html_block) => {
+                let html_block = std::mem::take(&mut self.html_block);
+                for part in details::parts(&html_block) {
+                    self.details_part(part)?;
+                }
+            },
+            TagEnd::HtmlBlock => {
+                // Comments can arrive split over several chunks, so judge the block as a whole
+                if self.args.show_comments && self.html_block.trim_start().starts_with("<!--") {
+                    let html_block = std::mem::take(&mut self.html_block);
+                    for line in html_block.lines() {
+                        if self.quote_depth > 0 {
+                            self.write_quote_prefix()?;
+                        } else {
+                            self.write_indent()?;
+                            if !self.lists.is_empty() {
+                                write!(self.stdout, "{:1$}", "", self.item_marker_width())?;
+                            }
+                        }
+                        self.stdout.set_color(&self.theme.comment)?;
+                        writeln!(self.stdout, "{}", line)?;
+                    }
+                    self.stdout.reset()?;
+                } else if !self.html_block.trim_start().starts_with("<!--") {
+                    self.degrade("raw HTML block");
+                }
+                self.html_block.clear();
+            },
+            TagEnd::TableHead | TagEnd::TableRow => {
+                // A row has ended; buffer it until the whole table is known
+                self.table_rows.push(Row {
+                    header: tag_end == TagEnd::TableHead,
+                    cells: std::mem::take(&mut self.current_row_cells),
+                });
+            },
+            TagEnd::MetadataBlock(_) if !self.args.render_frontmatter_as_table => self.metadata = None,
+            TagEnd::MetadataBlock(_) => {
+                let yaml = self.metadata.take().unwrap_or_default();
+                match frontmatter::rows(&yaml) {
+                    Some(rows) => {
+                        let max_width = self.max_table_width(&Cell::default());
+                        write_table(
+                            &mut self.stdout,
+                            &rows,
+                            &[Alignment::None, Alignment::None],
+                            &self.theme.table_header,
+                            &self.theme.table_border,
+                            self.args.zebra.then_some(&self.theme.table_stripe),
+                            &Cell::default(),
+                            max_width,
+                        )?;
+                        self.block_break(1)?;
+                    }
+                    None if self.args.verbose => eprintln!("front matter is not a YAML mapping, not shown"),
+                    None => {}
+                }
+            },
+            TagEnd::Table if self.args.tables_as_csv => {
+                write_csv(&mut self.stdout, &self.table_rows)?;
+                self.in_table = false;
+                self.table_alignments.clear();
+                self.table_rows.clear();
+            },
+            TagEnd::Table => {
+                let prefix = self.table_prefix();
+                if self.args.collapse_tables && table_width(&self.table_rows) + prefix.width() > self.width {
+                    write_expanded(
+                        &mut self.stdout,
+                        &self.table_rows,
+                        &self.theme.table_header,
+                        &self.theme.table_border,
+                        &prefix,
+                    )?;
+                } else {
+                    let max_width = self.max_table_width(&prefix);
+                    write_table(
+                        &mut self.stdout,
+                        &self.table_rows,
+                        &self.table_alignments,
+                        &self.theme.table_header,
+                        &self.theme.table_border,
+                        self.args.zebra.then_some(&self.theme.table_stripe),
+                        &prefix,
+                        max_width,
+                    )?;
+                }
+                self.in_table = false;
+                self.table_alignments.clear();
+                self.table_rows.clear();
+                self.block_break(1)?; // Add a newline after the table
+            },
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn text(&mut self, text: &str) -> io::Result<()> {
+        if let Some(metadata) = &mut self.metadata {
+            metadata.push_str(text);
+        } else if self.hide_code {
+            // The diagram's summary stands in for its source
+        } else if self.in_code_block && self.args.fold_code {
+            self.code_buffer.push_str(text);
+        } else if self.in_table {
+            // When in a table, accumulate text for the current cell
+            self.push_cell(text);
+        } else {
+            if self.setext_underline.is_some() {
+                self.heading_width += term::text_width(text);
+            }
+            if self.stdout.at_line_start() && !self.in_list && !self.no_tab && self.quote_depth == 0 && !self.in_code {
+                self.write_indent()?;
+                if self.in_code_block {
+                    self.stdout.set_color(&self.theme.code)?; // Restore after indent guides
+                }
+            }
+            if self.in_code {
+                self.in_code = false;
+                write!(self.stdout, "~")?;
+            }
+            if self.args.nested_quote_colors && self.quote_depth > 0 && !self.in_code_block && self.stdout.color().is_none() {
+                self.stdout.set_color(&self.quote_color())?; // Plain quoted text takes its depth's color
+            }
+            if self.completed_task && self.args.completed_tasks != CompletedTasks::Plain {
+                // Layered over whatever inline style is active
+                let mut overlay = ColorSpec::new();
+                overlay.set_reset(false);
+                self.style_completed_task(&mut overlay);
+                self.stdout.set_color(&overlay)?;
+            }
+            if self.in_code_block {
+                self.write_code_lines(text)?;
+            } else if self.args.wrap && !self.in_code_block {
+                self.write_wrapped(text)?;
+            } else {
+                write!(self.stdout, "{}", text)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Write code block text as the source has it, each line after the
+    /// block's indentation (the first line's is already written).
+    fn write_code_lines(&mut self, text: &str) -> io::Result<()> {
+        for line in text.split_inclusive('\n') {
+            if self.stdout.at_line_start() && !self.in_list && self.quote_depth == 0 && self.text_level > 0 {
+                self.write_indent()?;
+                if self.args.indent_char.is_some() {
+                    self.stdout.set_color(&self.theme.code)?; // Restore after indent guides
+                }
+            }
+            let (content, tag) = match self.code_lang_tag.take() {
+                Some(tag) if line.ends_with('\n') => (&line[..line.len() - 1], Some(tag)),
+                tag => {
+                    self.code_lang_tag = tag;
+                    (line, None)
+                }
+            };
+            if self.marked_lines.is_empty() {
+                self.write_code(content)?;
+            } else {
+                self.write_marked_code(content)?;
+            }
+            if let Some(tag) = tag {
+                self.write_code_lang_tag(&tag)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// End the first line of a code block with its language, dimmed and
+    /// right-aligned, or a space after the code if the line is too long.
+    fn write_code_lang_tag(&mut self, tag: &str) -> io::Result<()> {
+        let padding = self.width.saturating_sub(self.stdout.column() + tag.width()).max(1);
+        self.stdout.set_color(&self.theme.code_lang)?;
+        writeln!(self.stdout, "{:padding$}{}", "", tag)?;
+        self.stdout.set_color(&self.theme.code)
+    }
+
+    /// Write code block text, highlighted if its language is.
+    fn write_code(&mut self, text: &str) -> io::Result<()> {
+        if self.highlight.is_some() {
+            self.write_highlighted(text)
+        } else if self.args.tab_size_for_code > 0 && text.contains('\t') {
+            write!(self.stdout, "{}", expand_tabs(text, self.args.tab_size_for_code, 0))
+        } else {
+            write!(self.stdout, "{}", text)
+        }
+    }
+
+    /// Write code block text line by line, giving the lines picked out by a
+    /// `{1,3-5}` annotation a background.
+    fn write_marked_code(&mut self, text: &str) -> io::Result<()> {
+        for line in text.split_inclusive('\n') {
+            self.code_line += 1;
+            let (content, newline) = line.split_at(line.trim_end_matches('\n').len());
+            self.line_marked = self.marked_lines.iter().any(|&(start, end)| (start..=end).contains(&self.code_line));
+            if self.line_marked {
+                let color = self.code_color(&self.theme.code);
+                self.stdout.set_color(&color)?;
+            }
+            self.write_code(content)?;
+            self.line_marked = false;
+            self.stdout.set_color(&self.theme.code)?; // The background stops at the line's end
+            write!(self.stdout, "{}", newline)?;
+        }
+        Ok(())
+    }
+
+    /// `color` for code on the current line, over the marked-line background
+    /// if the line is marked.
+    fn code_color(&self, color: &ColorSpec) -> ColorSpec {
+        let mut color = color.clone();
+        if self.line_marked {
+            color.set_bg(self.theme.marked_line.bg().copied());
+        }
+        color
+    }
+
+    /// Dim or strike through `color` inside a checked task item, as
+    /// --completed-tasks asks.
+    fn style_completed_task(&self, color: &mut ColorSpec) {
+        if !self.completed_task {
+            return;
+        }
+        match self.args.completed_tasks {
+            CompletedTasks::Plain => {}
+            CompletedTasks::Dim => {
+                color.set_dimmed(true);
+            }
+            CompletedTasks::Strike => {
+                color.set_strikethrough(true);
+            }
+        }
+    }
+
+    /// Columns of the innermost open list item's marker.
+    fn item_marker_width(&self) -> usize {
+        self.item_marker_widths.last().copied().unwrap_or(0)
+    }
+
+    /// Spaces past the block's indentation that wrapped lines start at.
+    fn wrap_indent(&self) -> usize {
+        match self.args.wrap_indent {
+            WrapIndent::Hang if !self.lists.is_empty() => self.item_marker_width(),
+            WrapIndent::Hang | WrapIndent::Flush => 0,
+            WrapIndent::Spaces(spaces) => spaces,
+        }
+    }
+
+    /// Write prose word by word, breaking the line before a word that would
+    /// run past the output width.
+    fn write_wrapped(&mut self, text: &str) -> io::Result<()> {
+        let mut continuation = self.indent_width() + self.wrap_indent();
+        if self.quote_depth > 0 {
+            continuation += quote_prefix(self.quote_depth, self.args.max_blockquote_depth, self.args.quote_marker()).width();
+        }
+        for word in text.split_inclusive(' ') {
+            let column = self.stdout.column();
+            // A word too long for any line is left to overflow rather than break forever
+            if column + term::text_width(word.trim_end()) > self.width && column > continuation {
+                self.wrap_line()?;
+                write!(self.stdout, "{}", word.trim_start())?;
+            } else {
+                write!(self.stdout, "{}", word)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Start a continuation line for wrapped text, keeping the active color.
+    fn wrap_line(&mut self) -> io::Result<()> {
+        let color = self.stdout.color().cloned();
+        writeln!(self.stdout)?;
+        if self.quote_depth > 0 {
+            self.write_quote_prefix()?;
+            self.quote_line_start = false; // Only source lines can be attributions
+        } else {
+            self.write_indent()?;
+        }
+        write!(self.stdout, "{}", " ".repeat(self.wrap_indent()))?;
+        if let Some(color) = color {
+            self.stdout.set_color(&color)?;
+        }
+        Ok(())
+    }
+
+    /// Write code block text in a highlighted language, coloring each piece
+    /// of a line by its kind. Unhighlighted pieces keep the code color.
+    fn write_highlighted(&mut self, text: &str) -> io::Result<()> {
+        let Some(highlighter) = &mut self.highlight else {
+            return Ok(());
+        };
+        let spans: Vec<_> = text.split_inclusive('\n').flat_map(|line| highlighter.spans(line)).collect();
+        for (kind, piece) in spans {
+            let color = match kind {
+                Kind::Plain => &self.theme.code,
+                Kind::Added => &self.theme.diff_added,
+                Kind::Removed => &self.theme.diff_removed,
+                Kind::Hunk => &self.theme.diff_hunk,
+                Kind::Key => &self.theme.config_key,
+                Kind::Section => &self.theme.config_section,
+                Kind::Comment => &self.theme.config_comment,
+                Kind::Prompt => &self.theme.shell_prompt,
+                Kind::Command => &self.theme.shell_command,
+                Kind::Output => &self.theme.shell_output,
+            };
+            let color = self.code_color(color);
+            self.stdout.set_color(&color)?;
+            if self.args.tab_size_for_code > 0 {
+                write!(self.stdout, "{}", expand_tabs(piece, self.args.tab_size_for_code, 0))?;
+            } else {
+                write!(self.stdout, "{}", piece)?;
+            }
+        }
+        self.stdout.set_color(&self.theme.code)
+    }
+
+    fn code(&mut self, code: &str) -> io::Result<()> {
+        if self.in_table {
+            let color = self.theme.code.clone();
+            if self.args.symbol {
+                self.push_cell_colored(&format!("`{}`", code), Some(&color));
+            } else {
+                self.push_cell_colored(code, Some(&color));
+            }
+        } else {
+            self.in_code = true;
+            if self.setext_underline.is_some() {
+                self.heading_width += term::text_width(code) + if self.args.symbol { 2 } else { 0 };
+            }
+            let mut color = self.theme.code.clone();
+            self.style_completed_task(&mut color);
+            self.stdout.set_color(&color)?;
+            if self.args.symbol {
+                write!(self.stdout, "`{}`", code)?;
+            } else {
+                write!(self.stdout, "{}", code)?;
+            }
+            self.stdout.reset()?;
+        }
+        Ok(())
+    }
+
+    /// Write body text, spelling out abbreviations with --abbr.
+    fn prose(&mut self, text: &str) -> io::Result<()> {
+        if !self.abbreviations.is_empty() && !self.in_code_block && self.metadata.is_none() {
+            self.abbreviated_text(text)
+        } else {
+            self.text(text)
+        }
+    }
+
+    /// Write body text with the glossary's terms picked out.
+    fn glossary_text(&mut self, mut text: &str) -> io::Result<()> {
+        while let Some((at, len)) = glossary::next_use(text, &self.glossary) {
+            self.prose(&text[..at])?;
+            let color = self.theme.glossary_term.clone();
+            if self.in_table {
+                self.cell_colors.push(color);
+                self.text(&text[at..at + len])?;
+                self.cell_colors.pop();
+            } else {
+                let restore = self.stdout.color().cloned();
+                self.stdout.set_color(&color)?;
+                self.text(&text[at..at + len])?;
+                match restore {
+                    Some(restore) => self.stdout.set_color(&restore)?,
+                    None => self.stdout.reset()?,
+                }
+            }
+            text = &text[at + len..];
+        }
+        self.prose(text)
+    }
+
+    /// Write prose, following the first use of each abbreviation with its
+    /// expansion: `HTML (HyperText Markup Language)`.
+    fn abbreviated_text(&mut self, mut text: &str) -> io::Result<()> {
+        while let Some((at, index)) = self.next_abbreviation(text) {
+            let (abbreviation, expansion) = self.abbreviations.remove(index);
+            let end = at + abbreviation.len();
+            self.text(&text[..end])?;
+            let color = self.theme.abbreviation.clone();
+            if self.in_table {
+                self.cell_colors.push(color);
+                self.text(&format!(" ({})", expansion))?;
+                self.cell_colors.pop();
+            } else {
+                let restore = self.stdout.color().cloned();
+                self.stdout.set_color(&color)?;
+                self.text(&format!(" ({})", expansion))?;
+                match restore {
+                    Some(restore) => self.stdout.set_color(&restore)?,
+                    None => self.stdout.reset()?,
+                }
+            }
+            text = &text[end..];
+        }
+        self.text(text)
+    }
+
+    /// Where the earliest abbreviation not yet spelled out is used as a whole
+    /// word in `text`, and its index in `abbreviations`.
+    fn next_abbreviation(&self, text: &str) -> Option<(usize, usize)> {
+        let is_word = |c: char| c.is_alphanumeric() || c == '_';
+        self.abbreviations
+            .iter()
+            .enumerate()
+            .filter_map(|(index, (abbreviation, _))| {
+                text.match_indices(abbreviation.as_str())
+                    .find(|&(at, _)| {
+                        !text[..at].ends_with(is_word) && !text[at + abbreviation.len()..].starts_with(is_word)
+                    })
+                    .map(|(at, _)| (at, index))
+            })
+            .min()
+    }
+
+    fn soft_break(&mut self) -> io::Result<()> {
+        if self.in_table {
+            // Soft breaks within table cells usually mean space
+            self.push_cell(" ");
+        } else if self.args.soft_break_as_space {
+            write!(self.stdout, " ")?; // Join source-wrapped lines, as HTML does
+        } else {
+            writeln!(self.stdout)?;
+            self.in_list = false;
+            if self.quote_depth > 0 {
+                self.write_quote_prefix()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn hard_break(&mut self) -> io::Result<()> {
+        if self.in_table {
+            self.push_cell(" "); // A newline would break the row layout
+        } else {
+            writeln!(self.stdout)?;
+            if self.quote_depth > 0 {
+                self.write_quote_prefix()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// With `--render-toc-with-links`, the document's headings as an outline
+    /// indented by level, each entry an OSC 8 link to its `#slug` when the
+    /// output supports hyperlinks.
+    pub fn contents(&mut self, headings: &[(HeadingLevel, String)]) -> io::Result<()> {
+        let Some(top) = headings.iter().map(|&(level, _)| level as usize).min() else {
+            return Ok(());
+        };
+        let linked = self.stdout.supports_hyperlinks();
+        self.stdout.set_color(&self.theme.heading)?;
+        writeln!(self.stdout, "Contents")?;
+        self.stdout.reset()?;
+        for (level, text) in headings {
+            write!(self.stdout, "{}", "  ".repeat(*level as usize - top + 1))?;
+            if linked {
+                let target = format!("#{}", validate::slug(text));
+                self.stdout.set_hyperlink(&HyperlinkSpec::open(target.as_bytes()))?;
+                self.stdout.set_color(&self.theme.anchor)?;
+                write!(self.stdout, "{}", text.trim())?;
+                self.stdout.reset()?;
+                self.stdout.set_hyperlink(&HyperlinkSpec::close())?;
+            } else {
+                write!(self.stdout, "{}", text.trim())?;
+            }
+            writeln!(self.stdout)?;
+        }
+        Ok(())
+    }
+
+    /// Separate the start of a `--label-regions` region with a rule and its label.
+    pub fn label_region(&mut self, region: Region) -> io::Result<()> {
+        self.after_heading = false;
+        self.block_break(1)?;
+        self.write_indent()?;
+        self.stdout.set_color(&self.theme.rule)?;
+        write!(self.stdout, "---")?;
+        self.stdout.set_color(&self.theme.region_label)?;
+        write!(self.stdout, " {}", region.label())?;
+        self.stdout.reset()?;
+        writeln!(self.stdout)
+    }
+
+    fn rule(&mut self) -> io::Result<()> {
+        self.after_heading = false;
+        self.block_break(1)?;
+        // Deeply indented rules are clamped so they never run past the right edge
+        let length = (3 * (self.text_level + 1)).min(self.width.saturating_sub(self.indent_width()).max(3));
+        let rule = "-".repeat(length);
+        if self.args.true_center {
+            self.stdout.capture();
+        }
+        self.write_indent()?;
+        self.stdout.set_color(&self.theme.rule)?;
+        write!(self.stdout, "{}", rule)?;
+        writeln!(self.stdout)?;
+        self.stdout.reset()?;
+        self.stdout.write_centered(self.width)?;
+        self.block_break(0)
+    }
+}
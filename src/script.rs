@@ -0,0 +1,119 @@
+//! Unicode superscript and subscript glyphs, for footnote markers and
+//! `<sup>` / `<sub>` text.
+
+/// Raised or lowered text.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Script {
+    Super,
+    Sub,
+}
+
+impl Script {
+    /// `text` in this script's glyphs, or `None` if any character has none.
+    pub fn glyphs(self, text: &str) -> Option<String> {
+        if text.is_empty() {
+            return None;
+        }
+        text.chars()
+            .map(|ch| match self {
+                Script::Super => superscript(ch),
+                Script::Sub => subscript(ch),
+            })
+            .collect()
+    }
+
+    /// `text` in glyphs where every character has one, otherwise written out
+    /// as `^(text)` or `_(text)`.
+    pub fn render(self, text: &str) -> String {
+        self.glyphs(text).unwrap_or_else(|| match self {
+            Script::Super => format!("^({})", text),
+            Script::Sub => format!("_({})", text),
+        })
+    }
+}
+
+fn superscript(ch: char) -> Option<char> {
+    let glyph = match ch {
+        '0' => '⁰',
+        '1' => '¹',
+        '2' => '²',
+        '3' => '³',
+        '4' => '⁴',
+        '5' => '⁵',
+        '6' => '⁶',
+        '7' => '⁷',
+        '8' => '⁸',
+        '9' => '⁹',
+        '+' => '⁺',
+        '-' => '⁻',
+        '=' => '⁼',
+        '(' => '⁽',
+        ')' => '⁾',
+        'a' => 'ᵃ',
+        'b' => 'ᵇ',
+        'c' => 'ᶜ',
+        'd' => 'ᵈ',
+        'e' => 'ᵉ',
+        'f' => 'ᶠ',
+        'g' => 'ᵍ',
+        'h' => 'ʰ',
+        'i' => 'ⁱ',
+        'j' => 'ʲ',
+        'k' => 'ᵏ',
+        'l' => 'ˡ',
+        'm' => 'ᵐ',
+        'n' => 'ⁿ',
+        'o' => 'ᵒ',
+        'p' => 'ᵖ',
+        'r' => 'ʳ',
+        's' => 'ˢ',
+        't' => 'ᵗ',
+        'u' => 'ᵘ',
+        'v' => 'ᵛ',
+        'w' => 'ʷ',
+        'x' => 'ˣ',
+        'y' => 'ʸ',
+        'z' => 'ᶻ',
+        _ => return None,
+    };
+    Some(glyph)
+}
+
+fn subscript(ch: char) -> Option<char> {
+    let glyph = match ch {
+        '0' => '₀',
+        '1' => '₁',
+        '2' => '₂',
+        '3' => '₃',
+        '4' => '₄',
+        '5' => '₅',
+        '6' => '₆',
+        '7' => '₇',
+        '8' => '₈',
+        '9' => '₉',
+        '+' => '₊',
+        '-' => '₋',
+        '=' => '₌',
+        '(' => '₍',
+        ')' => '₎',
+        'a' => 'ₐ',
+        'e' => 'ₑ',
+        'h' => 'ₕ',
+        'i' => 'ᵢ',
+        'j' => 'ⱼ',
+        'k' => 'ₖ',
+        'l' => 'ₗ',
+        'm' => 'ₘ',
+        'n' => 'ₙ',
+        'o' => 'ₒ',
+        'p' => 'ₚ',
+        'r' => 'ᵣ',
+        's' => 'ₛ',
+        't' => 'ₜ',
+        'u' => 'ᵤ',
+        'v' => 'ᵥ',
+        'x' => 'ₓ',
+        _ => return None,
+    };
+    Some(glyph)
+}
@@ -0,0 +1,186 @@
+//! Preprocessing applied to the raw Markdown source before it is parsed.
+
+use std::str::FromStr;
+
+use encoding_rs::{Encoding, UTF_8};
+
+/// An inclusive, 1-based range of source lines, as given to `--lines`.
+/// Either end may be left out: `200:` runs to the end, `:40` starts at the top.
+#[derive(Clone, Copy, Debug)]
+pub struct LineRange {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl FromStr for LineRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (start, end) = s.split_once(':').ok_or("expected START:END")?;
+        let bound = |value: &str, default: usize| -> Result<usize, String> {
+            if value.is_empty() {
+                Ok(default)
+            } else {
+                value.parse().map_err(|_| format!("invalid line number `{}`", value))
+            }
+        };
+        let range = LineRange { start: bound(start, 1)?.max(1), end: bound(end, usize::MAX)? };
+        if range.start > range.end {
+            return Err(format!("START ({}) is after END ({})", range.start, range.end));
+        }
+        Ok(range)
+    }
+}
+
+/// The character encoding of the input file, as given to `--encoding`.
+#[derive(Clone, Copy, Debug)]
+pub enum InputEncoding {
+    /// UTF-8, or UTF-16 when the file starts with a UTF-16 byte order mark.
+    Auto,
+    /// Any encoding known to `encoding_rs`, such as `windows-1252` or `utf-16le`.
+    Named(&'static Encoding),
+}
+
+impl FromStr for InputEncoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("auto") {
+            return Ok(InputEncoding::Auto);
+        }
+        Encoding::for_label(s.as_bytes())
+            .map(InputEncoding::Named)
+            .ok_or_else(|| format!("unknown encoding `{}`", s))
+    }
+}
+
+/// Decode the raw file bytes to UTF-8, replacing invalid sequences with U+FFFD.
+pub fn decode(bytes: &[u8], encoding: InputEncoding) -> String {
+    match encoding {
+        // `decode` switches to whatever encoding a byte order mark names
+        InputEncoding::Auto => UTF_8.decode(bytes).0.into_owned(),
+        InputEncoding::Named(encoding) => encoding.decode_with_bom_removal(bytes).0.into_owned(),
+    }
+}
+
+/// Convert `\r\n` and lone `\r` line endings to `\n`. pulldown-cmark accepts
+/// all three, but passes them through verbatim in raw HTML.
+pub fn normalize_line_endings(source: &str) -> String {
+    source.replace("\r\n", "\n").replace('\r', "\n")
+}
+
+/// The fence marker (```` ``` ```` or `~~~`, possibly longer) opening or closing a code block.
+fn fence_marker(line: &str) -> Option<&str> {
+    let trimmed = line.trim_start();
+    let ch = trimmed.chars().next().filter(|&c| c == '`' || c == '~')?;
+    let len = trimmed.len() - trimmed.trim_start_matches(ch).len();
+    (len >= 3).then(|| &trimmed[..len])
+}
+
+/// Whether a line starts a list item (`-`, `*`, `+`, `1.` or `1)`).
+fn is_list_item(line: &str) -> bool {
+    let trimmed = line.trim_start();
+    if let Some(rest) = trimmed.strip_prefix(['-', '*', '+']) {
+        return rest.is_empty() || rest.starts_with(' ');
+    }
+    let digits = trimmed.len() - trimmed.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    digits > 0 && trimmed[digits..].starts_with(['.', ')'])
+}
+
+/// Whether a line belongs to a list: an item or an indented continuation.
+fn in_list(line: &str) -> bool {
+    is_list_item(line) || (line.starts_with([' ', '\t']) && !line.trim().is_empty())
+}
+
+/// Keep only the lines in `range`, clamped to the document, warning on stderr
+/// when the cut leaves a code fence open or splits a list.
+pub fn select_lines(source: &str, range: LineRange) -> String {
+    let lines: Vec<&str> = source.lines().collect();
+    let start = range.start.min(lines.len() + 1) - 1;
+    let end = range.end.min(lines.len());
+    let selected = lines.get(start..end).unwrap_or_default();
+
+    // Track fences through the whole prefix so a selection starting inside one is noticed
+    let mut open_fence: Option<(&str, usize)> = None;
+    let mut starts_in_fence = false;
+    for (number, line) in lines[..end].iter().enumerate() {
+        if number == start
+            && let Some((_, opened)) = open_fence
+        {
+            starts_in_fence = true;
+            eprintln!("warning: --lines starts inside the code fence opened on line {}", opened + 1);
+        }
+        match (open_fence, fence_marker(line)) {
+            (None, Some(marker)) => open_fence = Some((marker, number)),
+            (Some((open, _)), Some(marker))
+                if marker.starts_with(open) && line.trim_start()[marker.len()..].trim().is_empty() =>
+            {
+                open_fence = None
+            }
+            _ => {}
+        }
+    }
+    if let Some((_, opened)) = open_fence
+        && opened >= start
+    {
+        eprintln!("warning: --lines ends inside the code fence opened on line {}", opened + 1);
+    }
+
+    // Indented code looks like list continuation, so lists are only checked outside fences
+    if !starts_in_fence && start > 0 && start < end && in_list(lines[start - 1]) && in_list(lines[start]) {
+        eprintln!("warning: --lines starts in the middle of a list at line {}", start + 1);
+    }
+    if open_fence.is_none() && end > start && end < lines.len() && in_list(lines[end - 1]) && in_list(lines[end]) {
+        eprintln!("warning: --lines ends in the middle of a list at line {}", end);
+    }
+
+    let mut slice = selected.join("\n");
+    slice.push('\n');
+    slice
+}
+
+/// An abbreviation definition line, `*[HTML]: HyperText Markup Language`.
+fn abbreviation(line: &str) -> Option<(String, String)> {
+    let rest = line.strip_prefix("   ").or_else(|| line.strip_prefix("  ")).or_else(|| line.strip_prefix(' ')).unwrap_or(line);
+    let (abbreviation, expansion) = rest.strip_prefix("*[")?.split_once("]:")?;
+    (!abbreviation.trim().is_empty()).then(|| (abbreviation.trim().to_string(), expansion.trim().to_string()))
+}
+
+/// Take the abbreviation definitions out of `source`, leaving blank lines in
+/// their place so line numbers still match. pulldown-cmark would otherwise
+/// show them as paragraphs. Lines inside code fences are left alone.
+pub fn extract_abbreviations(source: &str) -> (String, Vec<(String, String)>) {
+    let mut abbreviations = Vec::new();
+    let mut open_fence: Option<&str> = None;
+    let mut stripped = String::with_capacity(source.len());
+    for line in source.split_inclusive('\n') {
+        match (open_fence, fence_marker(line)) {
+            (None, Some(marker)) => open_fence = Some(marker),
+            (Some(open), Some(marker)) if marker.starts_with(open) && line.trim_start()[marker.len()..].trim().is_empty() => {
+                open_fence = None
+            }
+            (None, None) => {
+                if let Some(definition) = abbreviation(line) {
+                    abbreviations.push(definition);
+                    stripped.push_str(&line[line.trim_end_matches(['\r', '\n']).len()..]);
+                    continue;
+                }
+            }
+            _ => {}
+        }
+        stripped.push_str(line);
+    }
+    (stripped, abbreviations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::normalize_line_endings;
+
+    #[test]
+    fn crlf_and_lone_cr_become_lf() {
+        assert_eq!(normalize_line_endings("a\r\nb\rc\n"), "a\nb\nc\n");
+        assert_eq!(normalize_line_endings("\r\r\n\r"), "\n\n\n");
+        assert!(!normalize_line_endings("<div>\r\nraw\r</div>\r\n").contains('\r'));
+    }
+}
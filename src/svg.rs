@@ -0,0 +1,248 @@
+//! `--to svg`: the rendered output as an SVG "terminal screenshot", for
+//! embedding in web pages.
+
+use std::fmt::Write as _;
+use std::io::{self, Write};
+
+use termcolor::{Color, ColorSpec, WriteColor};
+
+use crate::term;
+use crate::theme::Theme;
+
+/// Font size in pixels, and the width and height of a character cell.
+const FONT_SIZE: f64 = 14.0;
+const CELL_WIDTH: f64 = 8.4;
+const LINE_HEIGHT: f64 = 19.0;
+/// Space around the text, inside the background.
+const PADDING: f64 = 16.0;
+/// Tab stops, as in a terminal.
+const TAB_WIDTH: usize = 8;
+
+/// A writer that keeps everything written to it as runs of styled text.
+#[derive(Default)]
+pub struct Recorder {
+    runs: Vec<(Option<ColorSpec>, Vec<u8>)>,
+    color: Option<ColorSpec>,
+}
+
+impl Write for Recorder {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self.runs.last_mut() {
+            Some((color, text)) if *color == self.color => text.extend_from_slice(buf),
+            _ => self.runs.push((self.color.clone(), buf.to_vec())),
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl WriteColor for Recorder {
+    fn supports_color(&self) -> bool {
+        true
+    }
+
+    fn set_color(&mut self, spec: &ColorSpec) -> io::Result<()> {
+        // A spec that doesn't reset is layered over the current style
+        let mut color = match &self.color {
+            Some(current) if !spec.reset() => current.clone(),
+            _ => ColorSpec::new(),
+        };
+        if let Some(fg) = spec.fg() {
+            color.set_fg(Some(*fg));
+        }
+        if let Some(bg) = spec.bg() {
+            color.set_bg(Some(*bg));
+        }
+        color.set_bold(color.bold() || spec.bold());
+        color.set_italic(color.italic() || spec.italic());
+        color.set_underline(color.underline() || spec.underline());
+        color.set_strikethrough(color.strikethrough() || spec.strikethrough());
+        color.set_dimmed(color.dimmed() || spec.dimmed());
+        color.set_intense(color.intense() || spec.intense());
+        self.color = Some(color);
+        Ok(())
+    }
+
+    fn reset(&mut self) -> io::Result<()> {
+        self.color = None;
+        Ok(())
+    }
+}
+
+/// The xterm color for a basic color, bright when `intense`.
+fn basic(index: u8, intense: bool) -> (u8, u8, u8) {
+    const NORMAL: [(u8, u8, u8); 8] = [
+        (0, 0, 0),
+        (205, 0, 0),
+        (0, 205, 0),
+        (205, 205, 0),
+        (0, 0, 238),
+        (205, 0, 205),
+        (0, 205, 205),
+        (229, 229, 229),
+    ];
+    const BRIGHT: [(u8, u8, u8); 8] = [
+        (127, 127, 127),
+        (255, 0, 0),
+        (0, 255, 0),
+        (255, 255, 0),
+        (92, 92, 255),
+        (255, 0, 255),
+        (0, 255, 255),
+        (255, 255, 255),
+    ];
+    if intense { BRIGHT[index as usize % 8] } else { NORMAL[index as usize % 8] }
+}
+
+/// A color as `#rrggbb`, using the xterm palette for indexed colors.
+fn hex(color: &Color, intense: bool) -> Option<String> {
+    let (r, g, b) = match *color {
+        Color::Black => basic(0, intense),
+        Color::Red => basic(1, intense),
+        Color::Green => basic(2, intense),
+        Color::Yellow => basic(3, intense),
+        Color::Blue => basic(4, intense),
+        Color::Magenta => basic(5, intense),
+        Color::Cyan => basic(6, intense),
+        Color::White => basic(7, intense),
+        Color::Ansi256(n @ 0..=7) => basic(n, intense),
+        Color::Ansi256(n @ 8..=15) => basic(n - 8, true),
+        Color::Ansi256(n @ 16..=231) => {
+            let level = |v: u8| if v == 0 { 0 } else { 55 + v * 40 };
+            let n = n - 16;
+            (level(n / 36), level(n / 6 % 6), level(n % 6))
+        }
+        Color::Ansi256(n) => {
+            let gray = 8 + (n - 232) * 10;
+            (gray, gray, gray)
+        }
+        Color::Rgb(r, g, b) => (r, g, b),
+        _ => return None,
+    };
+    Some(format!("#{:02x}{:02x}{:02x}", r, g, b))
+}
+
+/// A length, rounded to keep float noise out of the markup.
+fn px(length: f64) -> f64 {
+    (length * 10.0).round() / 10.0
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+impl Recorder {
+    /// The recorded text split into lines of styled runs, tabs expanded.
+    fn lines(&self) -> Vec<Vec<(Option<&ColorSpec>, String)>> {
+        let mut lines = vec![Vec::new()];
+        let mut column = 0;
+        for (color, bytes) in &self.runs {
+            for (i, piece) in String::from_utf8_lossy(bytes).split('\n').enumerate() {
+                if i > 0 {
+                    lines.push(Vec::new());
+                    column = 0;
+                }
+                let mut text = String::new();
+                for ch in piece.chars().filter(|&ch| ch != '\r') {
+                    if ch == '\t' {
+                        let spaces = TAB_WIDTH - column % TAB_WIDTH;
+                        text.push_str(&" ".repeat(spaces));
+                        column += spaces;
+                    } else {
+                        text.push(ch);
+                        column += term::text_width(ch.encode_utf8(&mut [0; 4]));
+                    }
+                }
+                if let Some(line) = lines.last_mut()
+                    && !text.is_empty()
+                {
+                    line.push((color.as_ref(), text));
+                }
+            }
+        }
+        if lines.last().is_some_and(Vec::is_empty) {
+            lines.pop();
+        }
+        lines
+    }
+
+    /// The recording as an SVG document on the theme's background.
+    pub fn to_svg(&self, theme: &Theme) -> String {
+        let lines = self.lines();
+        let columns = lines
+            .iter()
+            .map(|line| line.iter().map(|(_, text)| term::text_width(text)).sum::<usize>())
+            .max()
+            .unwrap_or(0);
+        let width = PADDING * 2.0 + columns as f64 * CELL_WIDTH;
+        let height = PADDING * 2.0 + lines.len() as f64 * LINE_HEIGHT;
+        let background = theme.background.bg().and_then(|color| hex(color, false)).unwrap_or_default();
+        let foreground = theme.background.fg().and_then(|color| hex(color, false)).unwrap_or_default();
+
+        let mut svg = String::new();
+        let _ = writeln!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}">"#,
+            w = px(width),
+            h = px(height)
+        );
+        let _ = writeln!(svg, r#"<rect width="100%" height="100%" rx="6" fill="{}"/>"#, background);
+        let _ = writeln!(
+            svg,
+            r#"<g font-family="ui-monospace, SFMono-Regular, Menlo, Consolas, monospace" font-size="{}" fill="{}" xml:space="preserve">"#,
+            FONT_SIZE, foreground
+        );
+        for (row, line) in lines.iter().enumerate() {
+            let top = PADDING + row as f64 * LINE_HEIGHT;
+            let baseline = top + LINE_HEIGHT - 5.0;
+            let mut column = 0;
+            let mut text = String::new();
+            for (color, run) in line {
+                let x = px(PADDING + column as f64 * CELL_WIDTH);
+                let run_width = term::text_width(run);
+                let mut attributes = String::new();
+                if let Some(color) = color {
+                    if let Some(bg) = color.bg().and_then(|bg| hex(bg, false)) {
+                        let _ = writeln!(
+                            svg,
+                            r#"<rect x="{}" y="{}" width="{}" height="{}" fill="{}"/>"#,
+                            x,
+                            top,
+                            px(run_width as f64 * CELL_WIDTH),
+                            LINE_HEIGHT,
+                            bg
+                        );
+                    }
+                    if let Some(fg) = color.fg().and_then(|fg| hex(fg, color.intense())) {
+                        let _ = write!(attributes, r#" fill="{}""#, fg);
+                    }
+                    if color.bold() {
+                        attributes.push_str(r#" font-weight="bold""#);
+                    }
+                    if color.italic() {
+                        attributes.push_str(r#" font-style="italic""#);
+                    }
+                    match (color.underline(), color.strikethrough()) {
+                        (true, true) => attributes.push_str(r#" text-decoration="underline line-through""#),
+                        (true, false) => attributes.push_str(r#" text-decoration="underline""#),
+                        (false, true) => attributes.push_str(r#" text-decoration="line-through""#),
+                        (false, false) => {}
+                    }
+                    if color.dimmed() {
+                        attributes.push_str(r#" opacity="0.6""#);
+                    }
+                }
+                let _ = write!(text, r#"<tspan x="{}"{}>{}</tspan>"#, x, attributes, escape(run));
+                column += run_width;
+            }
+            if !text.is_empty() {
+                let _ = writeln!(svg, r#"<text y="{}">{}</text>"#, baseline, text);
+            }
+        }
+        svg.push_str("</g>\n</svg>\n");
+        svg
+    }
+}
@@ -0,0 +1,293 @@
+//! Buffered table model: cells keep their inline styling as segments and the
+//! whole table is printed once every row is known.
+
+use std::io::{self, Write};
+
+use pulldown_cmark::Alignment;
+use termcolor::{ColorSpec, WriteColor};
+
+use crate::term;
+
+/// A run of table cell text rendered with a single color.
+#[derive(Clone)]
+pub struct Segment {
+    pub text: String,
+    pub color: Option<ColorSpec>,
+}
+
+/// A table cell, kept as styled segments so inline styling survives buffering.
+#[derive(Clone, Default)]
+pub struct Cell {
+    pub segments: Vec<Segment>,
+}
+
+impl Cell {
+    /// A cell holding unstyled text.
+    pub fn plain(text: &str) -> Self {
+        let mut cell = Cell::default();
+        cell.push(text, None);
+        cell
+    }
+
+    pub fn push(&mut self, text: &str, color: Option<&ColorSpec>) {
+        if let Some(last) = self.segments.last_mut()
+            && last.color.as_ref() == color
+        {
+            last.text.push_str(text);
+            return;
+        }
+        self.segments.push(Segment { text: text.to_string(), color: color.cloned() });
+    }
+
+    /// Display width of the cell text, ignoring any styling.
+    pub fn width(&self) -> usize {
+        self.segments.iter().map(|segment| term::text_width(&segment.text)).sum()
+    }
+
+    /// The cell text without its styling.
+    pub fn text(&self) -> String {
+        self.segments.iter().map(|segment| segment.text.as_str()).collect()
+    }
+
+    /// The cell cut down to at most `width` columns, ending in `…` when
+    /// anything had to go.
+    fn truncated(&self, width: usize) -> Cell {
+        if self.width() <= width {
+            return self.clone();
+        }
+        let mut cell = Cell::default();
+        let mut room = width.saturating_sub(1); // Keep a column for the ellipsis
+        'segments: for segment in &self.segments {
+            let mut text = String::new();
+            for ch in segment.text.chars() {
+                let ch_width = term::text_width(ch.encode_utf8(&mut [0; 4]));
+                if ch_width > room {
+                    cell.push(&text, segment.color.as_ref());
+                    break 'segments;
+                }
+                room -= ch_width;
+                text.push(ch);
+            }
+            cell.push(&text, segment.color.as_ref());
+        }
+        cell.push("…", None);
+        cell
+    }
+}
+
+/// A buffered table row; `header` marks the row coming from `TableHead`.
+pub struct Row {
+    pub header: bool,
+    pub cells: Vec<Cell>,
+}
+
+/// The width of each column: its widest cell across all rows.
+fn column_widths(rows: &[Row]) -> Vec<usize> {
+    let mut column_widths: Vec<usize> = Vec::new();
+    for row in rows {
+        for (i, cell) in row.cells.iter().enumerate() {
+            if i >= column_widths.len() {
+                column_widths.push(0);
+            }
+            column_widths[i] = column_widths[i].max(cell.width());
+        }
+    }
+    column_widths
+}
+
+/// Shrink the widest columns one column at a time until the table, borders
+/// included, fits in `max_width`. Every column keeps at least one column.
+fn fit_columns(column_widths: &mut [usize], max_width: usize) {
+    let borders = column_widths.len() + 1;
+    while column_widths.iter().sum::<usize>() + borders > max_width {
+        let Some(widest) = column_widths.iter_mut().rev().max().filter(|width| **width > 1) else {
+            break;
+        };
+        *widest -= 1;
+    }
+}
+
+/// Columns the table takes up once printed, borders included.
+pub fn table_width(rows: &[Row]) -> usize {
+    let column_widths = column_widths(rows);
+    column_widths.iter().sum::<usize>() + column_widths.len() + 1
+}
+
+/// `color` on the background of `stripe`, if the row is shaded.
+fn shaded(color: &ColorSpec, stripe: Option<&ColorSpec>) -> ColorSpec {
+    let mut shaded = color.clone();
+    if let Some(stripe) = stripe {
+        shaded.set_bg(stripe.bg().copied());
+    }
+    shaded
+}
+
+/// Print a cell's segments, using `header_color` for unstyled header text,
+/// on the background of `stripe` in a shaded row.
+fn write_segments<W: WriteColor>(
+    stdout: &mut W,
+    cell: &Cell,
+    header: bool,
+    header_color: &ColorSpec,
+    stripe: Option<&ColorSpec>,
+) -> io::Result<()> {
+    for segment in &cell.segments {
+        match (&segment.color, header) {
+            (Some(color), _) => stdout.set_color(&shaded(color, stripe))?,
+            (None, true) => stdout.set_color(&shaded(header_color, stripe))?,
+            (None, false) if stripe.is_some() => stdout.set_color(&shaded(&ColorSpec::new(), stripe))?,
+            (None, false) => stdout.reset()?,
+        }
+        write!(stdout, "{}", segment.text)?;
+    }
+    stdout.reset()
+}
+
+/// Print the start of a table line; nothing at all for top-level tables.
+fn write_prefix<W: WriteColor>(stdout: &mut W, prefix: &Cell) -> io::Result<()> {
+    if prefix.segments.is_empty() {
+        return Ok(());
+    }
+    write_segments(stdout, prefix, false, &ColorSpec::new(), None)
+}
+
+/// Print a fully buffered table, sizing every column from all of its rows.
+/// Every line starts with `prefix`, the indentation of the surrounding block.
+/// With `max_width`, the widest columns are narrowed to fit in it and the
+/// cells that no longer fit are truncated with `…`. With `stripe`, every
+/// other body row is shaded with its background, borders and padding included.
+#[allow(clippy::too_many_arguments)]
+pub fn write_table<W: WriteColor>(
+    stdout: &mut W,
+    rows: &[Row],
+    alignments: &[Alignment],
+    header_color: &ColorSpec,
+    border_color: &ColorSpec,
+    stripe: Option<&ColorSpec>,
+    prefix: &Cell,
+    max_width: Option<usize>,
+) -> io::Result<()> {
+    let mut column_widths = column_widths(rows);
+    if let Some(max_width) = max_width {
+        fit_columns(&mut column_widths, max_width);
+    }
+
+    let mut body_rows = 0;
+    for row in rows {
+        let stripe = stripe.filter(|_| !row.header && body_rows % 2 == 1);
+        if !row.header {
+            body_rows += 1;
+        }
+        let border_color = &shaded(border_color, stripe);
+        let padding_color = &shaded(&ColorSpec::new(), stripe);
+        write_prefix(stdout, prefix)?;
+        stdout.set_color(border_color)?;
+        write!(stdout, "|")?;
+
+        for (i, cell) in row.cells.iter().enumerate() {
+            let cell = &cell.truncated(column_widths[i]);
+            let padding = column_widths[i] - cell.width();
+            let (left, right) = match alignments.get(i) {
+                Some(Alignment::Center) => (padding / 2, padding - padding / 2),
+                Some(Alignment::Right) => (padding, 0),
+                _ => (0, padding), // Default to left
+            };
+            stdout.set_color(padding_color)?; // Plain, or shaded, after the border
+            write!(stdout, "{:left$}", "")?;
+            write_segments(stdout, cell, row.header, header_color, stripe)?;
+            if stripe.is_some() {
+                stdout.set_color(padding_color)?;
+            }
+            write!(stdout, "{:right$}", "")?;
+            stdout.set_color(border_color)?;
+            write!(stdout, "|")?;
+        }
+        stdout.reset()?; // Before the line ends, so the shading doesn't bleed
+        writeln!(stdout)?;
+
+        if row.header {
+            // Print the header separator line
+            write_prefix(stdout, prefix)?;
+            stdout.set_color(border_color)?;
+            write!(stdout, "|")?;
+            for (i, &width) in column_widths.iter().enumerate() {
+                // Exactly as wide as the column: a one-column centered one
+                // only has room for the left colon, and an empty one for none
+                let separator = match alignments.get(i) {
+                    Some(Alignment::Center) if width >= 2 => format!(":{:-<1$}:", "", width - 2),
+                    Some(Alignment::Left | Alignment::Center) if width >= 1 => format!(":{:-<1$}", "", width - 1),
+                    Some(Alignment::Right) if width >= 1 => format!("{:-<1$}:", "", width - 1),
+                    _ => format!("{:-<width$}", ""), // Default
+                };
+                write!(stdout, "{}", separator)?;
+                write!(stdout, "|")?;
+            }
+            writeln!(stdout)?;
+            stdout.reset()?;
+        }
+    }
+    Ok(())
+}
+
+/// Print each body row as its own block of `key | value` lines, keyed by the
+/// header cells, like psql's expanded display. Used for tables too wide to fit.
+/// Every line starts with `prefix`, as in `write_table`.
+pub fn write_expanded<W: WriteColor>(
+    stdout: &mut W,
+    rows: &[Row],
+    header_color: &ColorSpec,
+    border_color: &ColorSpec,
+    prefix: &Cell,
+) -> io::Result<()> {
+    let header = rows.iter().find(|row| row.header).map(|row| row.cells.as_slice()).unwrap_or_default();
+    let columns = rows.iter().map(|row| row.cells.len()).max().unwrap_or(0);
+    // Columns without a header cell are keyed by their number
+    let keys: Vec<Cell> = (0..columns)
+        .map(|i| match header.get(i) {
+            Some(cell) if cell.width() > 0 => cell.clone(),
+            _ => Cell::plain(&(i + 1).to_string()),
+        })
+        .collect();
+    let key_width = keys.iter().map(Cell::width).max().unwrap_or(0);
+
+    for (record, row) in rows.iter().filter(|row| !row.header).enumerate() {
+        let value_width = row.cells.iter().map(Cell::width).max().unwrap_or(0);
+        let title = format!("-[ RECORD {} ]", record + 1);
+        write_prefix(stdout, prefix)?;
+        stdout.set_color(border_color)?;
+        writeln!(stdout, "{:-<1$}", title, (key_width + 3 + value_width).max(title.len()))?;
+        stdout.reset()?;
+        for (key, cell) in keys.iter().zip(&row.cells) {
+            write_prefix(stdout, prefix)?;
+            write_segments(stdout, key, true, header_color, None)?;
+            write!(stdout, "{:1$}", "", key_width - key.width())?;
+            stdout.set_color(border_color)?;
+            write!(stdout, " | ")?;
+            stdout.reset()?;
+            write_segments(stdout, cell, false, header_color, None)?;
+            writeln!(stdout)?;
+        }
+    }
+    Ok(())
+}
+
+/// Write the rows as RFC 4180 CSV, one record per line. Fields with commas,
+/// quotes or line breaks are quoted, with their quotes doubled.
+pub fn write_csv<W: Write>(out: &mut W, rows: &[Row]) -> io::Result<()> {
+    for row in rows {
+        let fields: Vec<String> = row
+            .cells
+            .iter()
+            .map(|cell| {
+                let text = cell.text();
+                if text.contains([',', '"', '\n', '\r']) {
+                    format!("\"{}\"", text.replace('"', "\"\""))
+                } else {
+                    text
+                }
+            })
+            .collect();
+        writeln!(out, "{}", fields.join(","))?;
+    }
+    Ok(())
+}
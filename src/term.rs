@@ -0,0 +1,115 @@
+//! Terminal geometry detection.
+
+use std::env;
+use std::sync::OnceLock;
+
+use terminal_size::{Width, terminal_size};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// Columns assumed when nothing else tells us the terminal width.
+const DEFAULT_WIDTH: usize = 80;
+
+/// Rows assumed when nothing else tells us the terminal height.
+#[cfg(feature = "interactive")]
+const DEFAULT_HEIGHT: usize = 24;
+
+/// Width available for output: an explicit `--width`, the detected terminal
+/// width, `$COLUMNS`, or 80, in that order.
+pub fn width(explicit: Option<usize>) -> usize {
+    explicit
+        .or_else(|| terminal_size().map(|(Width(columns), _)| columns as usize))
+        .or_else(|| env::var("COLUMNS").ok()?.trim().parse().ok())
+        .filter(|&columns| columns > 0)
+        .unwrap_or(DEFAULT_WIDTH)
+}
+
+/// Rows available for paging: the detected terminal height, `$LINES`, or 24,
+/// in that order.
+#[cfg(feature = "interactive")]
+pub fn height() -> usize {
+    terminal_size()
+        .map(|(_, terminal_size::Height(rows))| rows as usize)
+        .or_else(|| env::var("LINES").ok()?.trim().parse().ok())
+        .filter(|&rows| rows > 0)
+        .unwrap_or(DEFAULT_HEIGHT)
+}
+
+/// Columns an emoji takes up, when set by `--wide-emoji`.
+static EMOJI_WIDTH: OnceLock<usize> = OnceLock::new();
+
+/// Lay out emoji as `width` columns wide from now on, for terminals that
+/// disagree with `unicode-width`. Only the layout math changes.
+pub fn set_emoji_width(width: usize) {
+    let _ = EMOJI_WIDTH.set(width);
+}
+
+/// Whether `ch` is shown as an emoji by default.
+fn is_emoji(ch: char) -> bool {
+    matches!(ch as u32, 0x1F000..=0x1FAFF | 0x2600..=0x27BF | 0x2B50 | 0x2B55 | 0x231A | 0x231B | 0x23E9..=0x23F3)
+}
+
+/// Display width of `text` in columns, counting each emoji (with any
+/// variation selector, skin tone and zero-width-joined parts) as the
+/// `--wide-emoji` width when one is set.
+pub fn text_width(text: &str) -> usize {
+    let Some(&emoji_width) = EMOJI_WIDTH.get() else {
+        return text.width();
+    };
+    let mut width = 0;
+    let mut chars = text.chars().peekable();
+    while let Some(ch) = chars.next() {
+        if !is_emoji(ch) && chars.peek() != Some(&'\u{FE0F}') {
+            width += ch.width().unwrap_or(0);
+            continue;
+        }
+        width += emoji_width;
+        // Swallow everything that joins this emoji into a single glyph
+        while let Some(&next) = chars.peek() {
+            match next {
+                '\u{FE0F}' | '\u{1F3FB}'..='\u{1F3FF}' => {
+                    chars.next();
+                }
+                '\u{200D}' => {
+                    chars.next();
+                    chars.next();
+                }
+                _ => break,
+            }
+        }
+    }
+    width
+}
+
+/// `text` without its ANSI escape sequences: colors and other CSI sequences,
+/// and OSC sequences such as hyperlinks.
+pub fn strip_escapes(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(ch) = chars.next() {
+        if ch != '\x1b' {
+            stripped.push(ch);
+            continue;
+        }
+        match chars.next() {
+            // CSI: parameters up to a final letter
+            Some('[') => while chars.next().is_some_and(|c| !c.is_ascii_alphabetic()) {},
+            // OSC: up to a BEL or an ESC \ terminator
+            Some(']') => {
+                while let Some(c) = chars.next() {
+                    if c == '\x07' || (c == '\x1b' && chars.next_if_eq(&'\\').is_some()) {
+                        break;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    stripped
+}
+
+/// The widest line's display width and the number of lines in rendered output.
+pub fn measure(rendered: &str) -> (usize, usize) {
+    let text = strip_escapes(rendered);
+    let width = text.lines().map(text_width).max().unwrap_or(0);
+    (width, text.lines().count())
+}
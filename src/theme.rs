@@ -0,0 +1,272 @@
+//! Colors for every styled Markdown element.
+
+use std::env;
+
+use clap::ValueEnum;
+use pulldown_cmark::BlockQuoteKind;
+use termcolor::{Color, ColorSpec};
+
+/// Glyphs prefixed to headings with `--heading-icons`, from H1 down to H6.
+/// The bars thin out as the level drops, and H5 and H6 get dots.
+pub const HEADING_ICONS: [&str; 6] = ["▍", "▎", "▏", "▏", "·", "·"];
+
+/// The color spec used for each kind of rendered element.
+pub struct Theme {
+    pub heading: ColorSpec,
+    /// Per-level heading colors for `--heading-color-by-level`, H1 first.
+    pub heading_levels: Vec<ColorSpec>,
+    pub strong: ColorSpec,
+    pub emphasis: ColorSpec,
+    pub strikethrough: ColorSpec,
+    pub blockquote: ColorSpec,
+    /// Per-depth quote colors for `--nested-quote-colors`, outermost first.
+    pub quote_levels: Vec<ColorSpec>,
+    pub attribution: ColorSpec,
+    /// Callout labels, by kind: note, tip, important, warning and caution
+    pub callouts: [ColorSpec; 5],
+    pub code: ColorSpec,
+    pub diff_added: ColorSpec,
+    pub diff_removed: ColorSpec,
+    pub diff_hunk: ColorSpec,
+    pub config_key: ColorSpec,
+    pub config_section: ColorSpec,
+    pub config_comment: ColorSpec,
+    pub shell_prompt: ColorSpec,
+    pub shell_command: ColorSpec,
+    pub shell_output: ColorSpec,
+    pub fence: ColorSpec,
+    /// The language tag of `--show-code-lang`
+    pub code_lang: ColorSpec,
+    /// The background of code lines picked out with `--highlight-lines`
+    pub marked_line: ColorSpec,
+    pub rule: ColorSpec,
+    pub table_header: ColorSpec,
+    pub table_border: ColorSpec,
+    /// The background of every other body row with `--zebra`
+    pub table_stripe: ColorSpec,
+    pub indent: ColorSpec,
+    pub comment: ColorSpec,
+    pub link_title: ColorSpec,
+    /// `#heading` links, with `--check-anchors`, that lead somewhere
+    pub anchor: ColorSpec,
+    pub broken_anchor: ColorSpec,
+    pub abbreviation: ColorSpec,
+    /// Terms in definition lists, and their uses elsewhere with `--glossary`
+    pub definition_term: ColorSpec,
+    pub glossary_term: ColorSpec,
+    pub details_summary: ColorSpec,
+    /// The done part of `--progress` bars
+    pub progress: ColorSpec,
+    /// `<kbd>` keys, drawn as keycaps
+    pub key: ColorSpec,
+    pub heading_attributes: ColorSpec,
+    pub region_label: ColorSpec,
+    /// The text and page colors of the terminal drawn by `--to svg`
+    #[cfg(feature = "svg")]
+    pub background: ColorSpec,
+}
+
+/// How many colors the terminal can show, for `--color-depth`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ColorDepth {
+    /// The 8 basic colors, with gray shown dimmed
+    #[value(name = "16")]
+    Basic,
+    /// The 256-color palette
+    #[value(name = "256")]
+    Palette,
+    /// 24-bit RGB colors
+    Truecolor,
+}
+
+impl ColorDepth {
+    /// Truecolor when `$COLORTERM` says the terminal supports it, otherwise
+    /// the 256-color palette.
+    pub fn detect() -> Self {
+        match env::var("COLORTERM").as_deref() {
+            Ok("truecolor" | "24bit") => ColorDepth::Truecolor,
+            _ => ColorDepth::Palette,
+        }
+    }
+}
+
+/// The colors the theme is made of, before they are mapped to a color depth.
+#[derive(Clone, Copy)]
+enum Logical {
+    Blue,
+    /// The darker blue of table borders and headers
+    Navy,
+    Cyan,
+    Green,
+    Yellow,
+    Magenta,
+    Red,
+    Gray,
+}
+
+impl Logical {
+    /// A foreground color spec showing this color at `depth`.
+    fn spec(self, depth: ColorDepth) -> ColorSpec {
+        let mut spec = ColorSpec::new();
+        let color = match (self, depth) {
+            (Logical::Gray, ColorDepth::Basic) => {
+                spec.set_dimmed(true); // The basic colors have no gray
+                return spec;
+            }
+            (Logical::Blue | Logical::Navy, ColorDepth::Basic) => Color::Blue,
+            (Logical::Blue, ColorDepth::Palette) => Color::Blue,
+            (Logical::Navy, ColorDepth::Palette) => Color::Ansi256(4),
+            (Logical::Gray, ColorDepth::Palette) => Color::Ansi256(8), // Dark gray / Bright Black
+            (Logical::Cyan, ColorDepth::Basic | ColorDepth::Palette) => Color::Cyan,
+            (Logical::Green, ColorDepth::Basic | ColorDepth::Palette) => Color::Green,
+            (Logical::Yellow, ColorDepth::Basic | ColorDepth::Palette) => Color::Yellow,
+            (Logical::Magenta, ColorDepth::Basic | ColorDepth::Palette) => Color::Magenta,
+            (Logical::Red, ColorDepth::Basic | ColorDepth::Palette) => Color::Red,
+            (Logical::Blue, ColorDepth::Truecolor) => Color::Rgb(97, 175, 239),
+            (Logical::Navy, ColorDepth::Truecolor) => Color::Rgb(64, 120, 192),
+            (Logical::Cyan, ColorDepth::Truecolor) => Color::Rgb(86, 182, 194),
+            (Logical::Green, ColorDepth::Truecolor) => Color::Rgb(152, 195, 121),
+            (Logical::Yellow, ColorDepth::Truecolor) => Color::Rgb(229, 192, 123),
+            (Logical::Magenta, ColorDepth::Truecolor) => Color::Rgb(198, 120, 221),
+            (Logical::Red, ColorDepth::Truecolor) => Color::Rgb(224, 108, 117),
+            (Logical::Gray, ColorDepth::Truecolor) => Color::Rgb(110, 118, 129),
+        };
+        spec.set_fg(Some(color));
+        spec
+    }
+}
+
+fn dimmed() -> ColorSpec {
+    let mut spec = ColorSpec::new();
+    spec.set_dimmed(true);
+    spec
+}
+
+impl Theme {
+    /// The theme with its colors shown at `depth`.
+    pub fn new(depth: ColorDepth) -> Self {
+        let fg = |color: Logical| color.spec(depth);
+        let mut heading = fg(Logical::Blue);
+        heading.set_bold(true);
+        let heading_levels = [Logical::Blue, Logical::Cyan, Logical::Green, Logical::Yellow, Logical::Magenta, Logical::Gray]
+            .into_iter()
+            .map(|color| {
+                let mut spec = fg(color);
+                spec.set_bold(true);
+                spec
+            })
+            .collect();
+        let quote_levels = [Logical::Magenta, Logical::Cyan, Logical::Green, Logical::Yellow].into_iter().map(fg).collect();
+        let callouts = [Logical::Blue, Logical::Green, Logical::Magenta, Logical::Yellow, Logical::Red].map(|color| {
+            let mut spec = fg(color);
+            spec.set_bold(true);
+            spec
+        });
+        let mut attribution = fg(Logical::Magenta);
+        attribution.set_italic(true);
+        let mut config_section = fg(Logical::Magenta);
+        config_section.set_bold(true);
+        let mut shell_command = fg(Logical::Cyan);
+        shell_command.set_bold(true);
+        let mut broken_anchor = fg(Logical::Red);
+        broken_anchor.set_underline(true);
+        let mut region_label = dimmed();
+        region_label.set_italic(true);
+        let mut definition_term = fg(Logical::Yellow);
+        definition_term.set_bold(true);
+        let mut glossary_term = fg(Logical::Yellow);
+        glossary_term.set_underline(true);
+        let mut details_summary = ColorSpec::new();
+        details_summary.set_bold(true);
+        let mut key = ColorSpec::new();
+        key.set_fg(Some(Color::Black)).set_bg(Some(Color::White));
+        let mut marked_line = ColorSpec::new();
+        marked_line.set_bg(Some(match depth {
+            ColorDepth::Basic => Color::Blue,
+            ColorDepth::Palette => Color::Ansi256(237),
+            ColorDepth::Truecolor => Color::Rgb(44, 49, 58),
+        }));
+        #[cfg(feature = "svg")]
+        let mut background = ColorSpec::new();
+        #[cfg(feature = "svg")]
+        background.set_fg(Some(Color::Rgb(171, 178, 191))).set_bg(Some(Color::Rgb(40, 44, 52)));
+        let mut table_stripe = ColorSpec::new();
+        table_stripe.set_bg(Some(match depth {
+            ColorDepth::Basic => Color::Black,
+            ColorDepth::Palette => Color::Ansi256(236),
+            ColorDepth::Truecolor => Color::Rgb(48, 52, 60),
+        }));
+        let mut table_header = fg(Logical::Navy);
+        table_header.set_bold(true);
+
+        Theme {
+            heading,
+            heading_levels,
+            strong: fg(Logical::Yellow),
+            emphasis: fg(Logical::Green),
+            strikethrough: fg(Logical::Red),
+            blockquote: fg(Logical::Magenta),
+            quote_levels,
+            attribution,
+            callouts,
+            code: fg(Logical::Cyan),
+            diff_added: fg(Logical::Green),
+            diff_removed: fg(Logical::Red),
+            diff_hunk: fg(Logical::Magenta),
+            config_key: fg(Logical::Blue),
+            config_section,
+            config_comment: dimmed(),
+            shell_prompt: dimmed(),
+            shell_command,
+            shell_output: ColorSpec::new(),
+            fence: fg(Logical::Gray),
+            code_lang: dimmed(),
+            marked_line,
+            rule: fg(Logical::Gray),
+            table_header,
+            table_border: fg(Logical::Navy),
+            table_stripe,
+            indent: dimmed(),
+            comment: dimmed(),
+            link_title: dimmed(),
+            anchor: fg(Logical::Cyan),
+            broken_anchor,
+            abbreviation: dimmed(),
+            definition_term,
+            glossary_term,
+            details_summary,
+            progress: fg(Logical::Green),
+            key,
+            heading_attributes: dimmed(),
+            region_label,
+            #[cfg(feature = "svg")]
+            background,
+        }
+    }
+
+    /// The color for a heading of `level` (1-6); levels past the end of
+    /// `heading_levels` use its last color.
+    pub fn heading_level(&self, level: usize) -> &ColorSpec {
+        let index = level.saturating_sub(1).min(self.heading_levels.len().saturating_sub(1));
+        self.heading_levels.get(index).unwrap_or(&self.heading)
+    }
+
+    /// The color for a blockquote nested `depth` levels deep; deeper quotes
+    /// than `quote_levels` covers use its last color.
+    pub fn quote_level(&self, depth: usize) -> &ColorSpec {
+        let index = depth.saturating_sub(1).min(self.quote_levels.len().saturating_sub(1));
+        self.quote_levels.get(index).unwrap_or(&self.blockquote)
+    }
+
+    /// The label color for a callout of `kind`.
+    pub fn callout(&self, kind: BlockQuoteKind) -> &ColorSpec {
+        let index = match kind {
+            BlockQuoteKind::Note => 0,
+            BlockQuoteKind::Tip => 1,
+            BlockQuoteKind::Important => 2,
+            BlockQuoteKind::Warning => 3,
+            BlockQuoteKind::Caution => 4,
+        };
+        &self.callouts[index]
+    }
+}
@@ -0,0 +1,228 @@
+//! `--validate`: structural warnings about a document, for pre-commit checks.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::ops::Range;
+use std::path::Path;
+
+use pulldown_cmark::{Event, HeadingLevel, LinkType, Options, Parser, Tag, TagEnd};
+
+/// A problem found in the source, at an approximate 1-based position.
+pub struct Warning {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+/// The slug a heading gets as a link anchor: lowercased, spaces turned into
+/// dashes and punctuation other than `-` and `_` dropped, as GitHub does.
+pub fn slug(text: &str) -> String {
+    text.trim()
+        .chars()
+        .filter_map(|c| match c {
+            ' ' => Some('-'),
+            c if c.is_alphanumeric() || c == '-' || c == '_' => Some(c),
+            _ => None,
+        })
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// The level and text of every heading in `events`, in document order.
+pub fn headings(events: &[Event]) -> Vec<(HeadingLevel, String)> {
+    let mut headings = Vec::new();
+    let mut heading: Option<(HeadingLevel, String)> = None;
+    for event in events {
+        match event {
+            Event::Start(Tag::Heading { level, .. }) => heading = Some((*level, String::new())),
+            Event::End(TagEnd::Heading(_)) => headings.extend(heading.take()),
+            Event::Text(text) | Event::Code(text) => {
+                if let Some((_, heading)) = &mut heading {
+                    heading.push_str(text);
+                }
+            }
+            _ => {}
+        }
+    }
+    headings
+}
+
+/// The slugs of every heading in `events`, which `#slug` links can point to.
+pub fn heading_slugs(events: &[Event]) -> HashSet<String> {
+    headings(events).iter().map(|(_, text)| slug(text)).collect()
+}
+
+/// The local file a link or image destination points to, if it is one rather
+/// than a URL, an address with some other scheme or an anchor in this document.
+fn local_path(dest: &str) -> Option<String> {
+    let path = dest.split(['#', '?']).next()?;
+    if path.is_empty() || path.contains("://") || path.starts_with("//") {
+        return None;
+    }
+    // `mailto:` and the like; a one-letter "scheme" is a Windows drive
+    if let Some((scheme, _)) = path.split_once(':')
+        && scheme.len() > 1
+        && scheme.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+    {
+        return None;
+    }
+    Some(path.replace("%20", " "))
+}
+
+/// Warnings about `source` in document order: duplicate heading slugs, skipped
+/// heading levels, empty links, links to headings that don't exist, local
+/// links and images whose file doesn't exist under `base`, table rows with the
+/// wrong number of cells and emphasis that is never closed.
+pub fn check(source: &str, options: Options, base: &Path) -> Vec<Warning> {
+    let mut found: Vec<(usize, String)> = Vec::new();
+    let mut heading: Option<(usize, String)> = None;
+    let mut headings: HashMap<String, usize> = HashMap::new();
+    let mut last_level = None;
+    let mut link: Option<(usize, bool)> = None; // Start offset, whether it has text
+    let mut anchors: Vec<(usize, String)> = Vec::new(); // `#slug` links and where they are
+    let mut columns = 0;
+    let mut row: Option<(Range<usize>, usize)> = None; // Row range, cells present
+    let mut delimiters: Option<Range<usize>> = None; // Run of unmatched `*`, `_` or `~`
+
+    for (event, range) in Parser::new_ext(source, options).into_offset_iter() {
+        let is_delimiter = matches!(&event, Event::Text(text)
+            if !text.is_empty() && text.chars().all(|c| matches!(c, '*' | '_' | '~')) && source[range.clone()] == **text);
+        match (&mut delimiters, is_delimiter) {
+            (Some(run), true) if run.end == range.start => run.end = range.end,
+            _ => {
+                if let Some(run) = delimiters.take() {
+                    found.push((run.start, format!("unclosed emphasis `{}`", &source[run])));
+                }
+                if is_delimiter {
+                    delimiters = Some(range.clone());
+                }
+            }
+        }
+
+        match event {
+            Event::Start(Tag::Heading { level, .. }) => {
+                let level = level as usize;
+                if let Some(last) = last_level
+                    && level > last + 1
+                {
+                    found.push((range.start, format!("heading level jumps from H{} to H{}", last, level)));
+                }
+                last_level = Some(level);
+                heading = Some((range.start, String::new()));
+            }
+            Event::End(TagEnd::Heading(_)) => {
+                if let Some((start, text)) = heading.take() {
+                    let slug = slug(&text);
+                    if let Some(&first) = headings.get(&slug) {
+                        let first_line = position(source, first).0;
+                        found.push((start, format!("heading slug `{}` is also used on line {}", slug, first_line)));
+                    } else {
+                        headings.insert(slug, start);
+                    }
+                }
+            }
+            Event::Start(Tag::Link { link_type, dest_url, .. }) => {
+                if dest_url.trim().is_empty() {
+                    found.push((range.start, "link has no destination".to_string()));
+                }
+                if link_type != LinkType::Email
+                    && let Some(path) = local_path(&dest_url)
+                    && !base.join(&path).exists()
+                {
+                    found.push((range.start, format!("linked file `{}` not found", path)));
+                }
+                if let Some(anchor) = dest_url.strip_prefix('#')
+                    && !anchor.is_empty()
+                {
+                    anchors.push((range.start, anchor.to_string()));
+                }
+                link = Some((range.start, false));
+            }
+            Event::End(TagEnd::Link) => {
+                if let Some((start, false)) = link.take() {
+                    found.push((start, "link has no text".to_string()));
+                }
+            }
+            Event::Start(Tag::Table(alignments)) => columns = alignments.len(),
+            Event::Start(Tag::TableRow) => row = Some((range, 0)),
+            // Missing cells are filled in with empty ones that take up no source
+            Event::Start(Tag::TableCell) if !range.is_empty() => {
+                if let Some((_, cells)) = &mut row {
+                    *cells += 1;
+                }
+            }
+            Event::End(TagEnd::TableCell) => {
+                // Cells past the header's count are dropped; look for them after the last kept one
+                if let Some((row_range, cells)) = &mut row
+                    && *cells == columns
+                    && !range.is_empty()
+                {
+                    let rest = source[range.end..row_range.end].trim();
+                    let rest = rest.strip_prefix('|').unwrap_or(rest);
+                    let rest = rest.strip_suffix('|').unwrap_or(rest);
+                    if !rest.trim().is_empty() {
+                        *cells += rest.split('|').count();
+                    }
+                }
+            }
+            Event::End(TagEnd::TableRow) => {
+                if let Some((row_range, cells)) = row.take()
+                    && cells != columns
+                {
+                    let noun = if cells == 1 { "cell" } else { "cells" };
+                    found.push((row_range.start, format!("table row has {} {} but the header has {}", cells, noun, columns)));
+                }
+            }
+            Event::Text(text) | Event::Code(text) => {
+                if let Some((_, heading)) = &mut heading {
+                    heading.push_str(&text);
+                }
+                if let Some((_, has_text)) = &mut link {
+                    *has_text |= !text.trim().is_empty();
+                }
+            }
+            Event::Start(Tag::Image { dest_url, .. }) => {
+                if let Some(path) = local_path(&dest_url)
+                    && !base.join(&path).exists()
+                {
+                    found.push((range.start, format!("image `{}` not found", path)));
+                }
+                if let Some((_, has_text)) = &mut link {
+                    *has_text = true;
+                }
+            }
+            _ => {}
+        }
+    }
+    if let Some(run) = delimiters {
+        found.push((run.start, format!("unclosed emphasis `{}`", &source[run])));
+    }
+    // Headings can come after the links to them, so these wait until all are known
+    for (start, anchor) in anchors {
+        if !headings.contains_key(&anchor) {
+            found.push((start, format!("link to missing heading `#{}`", anchor)));
+        }
+    }
+
+    found.sort_by_key(|&(offset, _)| offset);
+    found
+        .into_iter()
+        .map(|(offset, message)| {
+            let (line, column) = position(source, offset);
+            Warning { line, column, message }
+        })
+        .collect()
+}
+
+/// The 1-based line and column of byte `offset` in `source`.
+fn position(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    (before.matches('\n').count() + 1, before[line_start..].chars().count() + 1)
+}
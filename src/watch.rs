@@ -0,0 +1,77 @@
+//! `--watch`: poll the input file and re-render it whenever it changes.
+
+use std::fs;
+use std::io::{self, Write};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use crate::Args;
+
+/// How often the file's modification time is checked.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+fn modified(path: &str) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+/// Render with `render`, then again every time the file's modification time
+/// changes. Runs until the process is interrupted or the output is closed.
+pub fn watch(args: &Args, render: fn(&Args) -> io::Result<()>) -> io::Result<()> {
+    let mut last_modified = modified(args.input_name());
+    report(render(args))?;
+    loop {
+        thread::sleep(POLL_INTERVAL);
+        // Editors often replace the file on save, so a missing file is just skipped
+        let current = modified(args.input_name());
+        if current.is_none() || current == last_modified {
+            continue;
+        }
+        last_modified = current;
+
+        if !args.preview_window_safe {
+            write!(io::stdout(), "\x1b[2J\x1b[H")?; // Clear the screen and home the cursor
+        }
+        if report(render(args))? && args.notify {
+            notify(args.input_name())?;
+        }
+    }
+}
+
+/// Whether a render succeeded. A failed one, such as a file caught halfway
+/// through being saved, is reported and the watch goes on; only a closed
+/// output ends it.
+fn report(rendered: io::Result<()>) -> io::Result<bool> {
+    match rendered {
+        Ok(()) => Ok(true),
+        Err(error) if error.kind() == io::ErrorKind::BrokenPipe => Err(error),
+        Err(error) => {
+            eprintln!("md-preview: {}", error);
+            Ok(false)
+        }
+    }
+}
+
+/// Tell the user the preview was refreshed.
+#[cfg(feature = "desktop-notify")]
+fn notify(file: &str) -> io::Result<()> {
+    let shown = notify_rust::Notification::new()
+        .summary("md-preview")
+        .body(&format!("{} was re-rendered", file))
+        .show();
+    match shown {
+        Ok(_) => Ok(()),
+        Err(_) => bell(), // No notification daemon available
+    }
+}
+
+/// Tell the user the preview was refreshed.
+#[cfg(not(feature = "desktop-notify"))]
+fn notify(_file: &str) -> io::Result<()> {
+    bell()
+}
+
+fn bell() -> io::Result<()> {
+    let mut stdout = io::stdout();
+    write!(stdout, "\x07")?;
+    stdout.flush()
+}
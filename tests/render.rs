@@ -0,0 +1,340 @@
+//! Renders the fixtures in `test/` with the built binary and checks the
+//! output for the behavior they exercise.
+
+use std::process::{Command, Output};
+
+/// Run md-preview from the crate root with `args`.
+fn md_preview(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_md-preview"))
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .args(args)
+        .output()
+        .expect("md-preview runs")
+}
+
+/// The uncolored output of rendering `fixture`, failing the test if
+/// md-preview doesn't exit successfully.
+fn render(fixture: &str, args: &[&str]) -> String {
+    let output = md_preview(&[args, &["--no-color", fixture]].concat());
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).expect("UTF-8 output")
+}
+
+#[test]
+fn heading_in_nested_list() {
+    let output = render("test/nested_list_heading.md", &[]);
+    assert!(output.contains("A heading inside the inner list"));
+    assert!(output.contains("- Outer item after the inner list closes"));
+}
+
+#[test]
+fn heading_in_definition() {
+    let output = render("test/glossary.md", &["--glossary"]);
+    assert!(output.contains("A heading inside a definition"));
+    assert!(output.contains("Text after the definition list."));
+}
+
+#[test]
+fn footnote_definitions_keep_their_labels() {
+    for args in [&[][..], &["--footnote-superscript"]] {
+        let output = render("test/footnotes.md", args);
+        assert!(output.contains("[^1]: The first footnote."));
+        assert!(output.contains("[^12]: The twelfth."));
+        assert!(output.contains("[^note]: A named footnote."));
+    }
+    assert!(render("test/footnotes.md", &["--footnote-superscript"]).contains("references¹ and¹²"));
+}
+
+#[test]
+fn quote_bar_leaves_the_file_argument_alone() {
+    assert!(render("test/quote_bar.md", &["--quote-bar"]).contains("▎ A quote"));
+    assert!(render("test/quote_bar.md", &["--quote-bar=|"]).contains("| A quote"));
+}
+
+#[test]
+fn wide_emoji_leaves_the_file_argument_alone() {
+    render("test/emoji_table.md", &["--wide-emoji"]);
+    render("test/emoji_table.md", &["--wide-emoji=1"]);
+}
+
+#[test]
+fn max_table_width_leaves_the_file_argument_alone() {
+    render("test/wide_table.md", &["--max-table-width"]);
+    let output = render("test/wide_table.md", &["--max-table-width=30"]);
+    assert!(output.lines().filter(|line| line.starts_with('|')).all(|line| line.chars().count() <= 30));
+}
+
+#[test]
+fn enable_flags_leave_the_file_argument_alone() {
+    let enabled = render("test/footnotes.md", &["--input-format", "commonmark", "--enable-footnotes"]);
+    assert!(enabled.contains("references[^1] and"));
+    assert!(enabled.contains("[^1]: The first footnote."));
+    // Unparsed, the definitions are plain text run together in one paragraph
+    let disabled = render("test/footnotes.md", &["--enable-footnotes=false"]);
+    assert!(disabled.contains("[^1]: The first footnote.\n[^12]"));
+}
+
+#[test]
+fn output_ends_with_one_newline() {
+    // A paragraph, a table, a list and a code block last
+    for fixture in ["test/setext.md", "test/wide_table.md", "test/tasks.md", "test/json.md"] {
+        for colors in [&["--no-color"][..], &["--to", "ansi"]] {
+            let output = md_preview(&[colors, &[fixture]].concat());
+            let output = String::from_utf8(output.stdout).expect("UTF-8 output");
+            assert!(output.ends_with('\n') && !output.ends_with("\n\n"), "{} {:?}: {:?}", fixture, colors, &output[output.len().saturating_sub(20)..]);
+        }
+    }
+}
+
+#[test]
+fn no_trailing_newline_drops_the_blank_lines_at_the_end() {
+    let output = md_preview(&["--no-color", "--no-trailing-newline", "test/wide_table.md"]);
+    assert!(!String::from_utf8_lossy(&output.stdout).ends_with("\n\n"));
+}
+
+#[test]
+fn no_carriage_returns_reach_the_output() {
+    for fixture in ["test/crlf.md", "test/cr_only.md"] {
+        for colors in [&["--no-color"][..], &["--to", "ansi"]] {
+            let output = md_preview(&[colors, &[fixture]].concat());
+            assert!(output.status.success());
+            assert!(!output.stdout.contains(&b'\r'), "{} {:?}", fixture, colors);
+        }
+    }
+}
+
+#[test]
+fn empty_documents_render_nothing() {
+    for fixture in ["test/empty.md", "test/whitespace_only.md", "test/comment_only.md"] {
+        let output = md_preview(&["--to", "ansi", "--verbose", fixture]);
+        assert!(output.status.success());
+        assert!(output.stdout.is_empty(), "{}: {:?}", fixture, String::from_utf8_lossy(&output.stdout));
+        assert!(String::from_utf8_lossy(&output.stderr).contains("empty document"), "{}", fixture);
+    }
+}
+
+#[test]
+fn link_bracket_styles() {
+    let square = render("test/link_brackets.md", &["--link-brackets", "square"]);
+    assert!(square.contains("A [plain link], a [titled link]"));
+    assert!(square.contains("an ![image] in"));
+    assert!(square.contains("|[in a table]|"));
+
+    let none = render("test/link_brackets.md", &["--link-brackets", "none"]);
+    assert!(none.contains("A plain link, a titled link"));
+    assert!(none.contains("an image in"));
+    assert!(none.contains("|in a table|"));
+
+    let angle = render("test/link_brackets.md", &["--link-brackets", "angle"]);
+    assert!(angle.contains("A ⟨plain link⟩, a ⟨titled link⟩"));
+    assert!(angle.contains("|⟨in a table⟩|"));
+}
+
+#[test]
+fn link_brackets_wrap_the_text_before_the_url() {
+    let output = render("test/link_brackets.md", &["--show-urls"]);
+    assert!(output.contains("[plain link](https://example.com)"));
+    assert!(output.contains("![image](logo.png)"));
+}
+
+#[test]
+fn link_delimiters_are_balanced() {
+    for args in [&[][..], &["--symbol"]] {
+        let output = render("test/link_delimiters.md", args);
+        for line in output.lines() {
+            let count = |c: char| line.matches(c).count();
+            assert_eq!(count('['), count(']'), "{:?}", line);
+            assert_eq!(count('('), count(')'), "{:?}", line);
+        }
+        assert!(output.contains("[plain], ["));
+        assert!(output.contains("![image] and a badge"));
+    }
+    // The setext underline is as long as the heading, delimiters, icon and
+    // attributes included
+    for (fixture, args) in [
+        ("test/link_delimiters.md", &["--symbol"][..]),
+        ("test/link_delimiters.md", &["--symbol", "--heading-icons"]),
+        ("test/heading_attributes.md", &["--symbol", "--show-heading-attributes"]),
+    ] {
+        let output = render(fixture, args);
+        let mut lines = output.lines().map(str::trim_start).skip_while(|line| !line.contains("Setext"));
+        let (heading, underline) = (lines.next().unwrap(), lines.next().unwrap());
+        assert_eq!(heading.chars().count(), underline.chars().count(), "{:?}:\n{}", args, output);
+    }
+}
+
+#[test]
+fn supported_elements_follow_the_color_options() {
+    let plain = md_preview(&["--list-supported-elements", "--no-color"]);
+    assert!(plain.status.success());
+    assert!(!plain.stdout.contains(&0x1b));
+    let colored = md_preview(&["--list-supported-elements", "--to", "ansi"]);
+    assert!(colored.stdout.contains(&0x1b));
+}
+
+#[test]
+fn supported_elements_follow_the_render_options() {
+    let list = |args: &[&str]| {
+        let output = md_preview(&[&["--list-supported-elements", "--no-color"], args].concat());
+        String::from_utf8(output.stdout).expect("UTF-8 output")
+    };
+    let row = |output: &str, name: &str| output.lines().find(|line| line.starts_with(&format!("|{} ", name))).unwrap_or_default().to_string();
+    assert!(row(&list(&[]), "Math").contains("|off "));
+    assert!(row(&list(&["--enable-math"]), "Math").contains("|no "));
+    assert!(row(&list(&["--math-unicode"]), "Math").contains("|yes "));
+    assert!(row(&list(&[]), "Task lists").contains("`[x]` / `[ ]`"));
+    assert!(row(&list(&["--checkbox-style", "ballot"]), "Task lists").contains("`☑` / `☐`"));
+    assert!(row(&list(&[]), "Callouts").contains("`[i] Note`"));
+    assert!(row(&list(&["--callout-style", "emoji"]), "Callouts").contains("`ℹ️ Note`"));
+}
+
+#[test]
+fn spacing_is_adjusted_once_per_gap() {
+    // Blank lines before each block's first line, at normal spacing
+    let starts = ["Heading", "Text under the heading.", "---", "- Item", "> Quote", "|A|B|", "Last", "End."];
+    let normal = [1, 0, 1, 0, 1, 1, 1, 0];
+    for (spacing, adjust) in [("tight", -1), ("normal", 0), ("loose", 1)] {
+        let output = render("test/spacing.md", &["--spacing", spacing]);
+        let lines: Vec<&str> = output.lines().collect();
+        for (start, blank_lines) in starts.iter().zip(normal) {
+            let at = lines.iter().position(|line| line == start).expect(start);
+            let before = lines[..at].iter().rev().take_while(|line| line.is_empty()).count();
+            assert_eq!(before as i32, (blank_lines + adjust).max(0), "{} before {:?}:\n{}", spacing, start, output);
+        }
+    }
+}
+
+#[test]
+fn tables_in_list_items_line_up_with_the_item_text() {
+    let output = render("test/list_tables.md", &[]);
+    let lines: Vec<&str> = output.lines().collect();
+    let item = lines.iter().find(|line| line.contains("Inner item with a table:")).unwrap();
+    let text_start = item.find('-').unwrap() + "- ".len();
+    let key_row = lines.iter().find(|line| line.contains("|Key")).unwrap();
+    assert_eq!(key_row.find('|'), Some(text_start), "{}", output);
+    let after = lines.iter().find(|line| line.contains("Text after the table.")).unwrap();
+    let text_start = after.len() - after.trim_start().len();
+    let step_row = lines.iter().find(|line| line.contains("|Step")).unwrap();
+    assert_eq!(step_row.find('|'), Some(text_start), "{}", output);
+}
+
+#[test]
+fn completed_tasks_strike_through_inline_code() {
+    let output = md_preview(&["--completed-tasks", "strike", "test/tasks.md"]);
+    let output = String::from_utf8(output.stdout).unwrap();
+    let line = output.lines().find(|line| line.contains("v1")).unwrap();
+    // The code span's own color comes after the reset that ends "Ship "
+    let code = line[..line.find("v1").unwrap()].rsplit("\x1b[0m").next().unwrap();
+    assert!(code.contains("\x1b[9m"), "{:?}", line);
+}
+
+#[test]
+fn mixed_lists_keep_each_levels_markers() {
+    let output = render("test/mixed_lists.md", &[]);
+    // Each item's indentation and marker, up to the space before its text
+    let markers: Vec<&str> = output.lines()
+        .filter(|line| line.trim_start().starts_with(|c: char| c == '-' || c.is_ascii_digit()))
+        .map(|line| line.split_once(' ').unwrap().0)
+        .collect();
+    assert_eq!(markers, ["1.", "\t-", "\t-", "\t\t1.", "\t\t2.", "\t-", "2.", "\t-", "3.", "7.", "\t-", "8."], "{}", output);
+}
+
+#[test]
+fn list_item_paragraphs_start_at_the_same_column() {
+    let output = render("test/list_paragraphs.md", &[]);
+    // Display column the given text starts at, with tab stops every 8
+    let column = |text: &str| {
+        let line = output.lines().find(|line| line.contains(text)).expect(text);
+        line[..line.find(text).unwrap()].chars().fold(0, |column, c| if c == '\t' { (column / 8 + 1) * 8 } else { column + 1 })
+    };
+    for (first, second) in [
+        ("First paragraph of the item.", "Second paragraph of the item."),
+        ("Numbered first.", "Numbered second."),
+        ("Nested", "Nested second."),
+        ("Numbered first.", "Back in the numbered item."),
+    ] {
+        assert_eq!(column(first), column(second), "{:?} and {:?}:\n{}", first, second, output);
+    }
+}
+
+#[test]
+fn setext_headings_render_like_atx_headings() {
+    let output = md_preview(&["test/setext.md"]);
+    let output = String::from_utf8(output.stdout).unwrap();
+    // Redundant resets aside, each setext heading's line is its ATX twin's
+    let line = |text: &str| output.lines().find(|line| line.contains(text)).expect(text).replace("\x1b[0m", "");
+    for level in ["Title", "Section"] {
+        let setext = line(&format!("Setext {}", level));
+        assert_eq!(setext.replace("Setext", "ATX"), line(&format!("ATX {}", level)), "{:?}", output);
+        assert!(setext.contains('\x1b'), "{:?}", setext);
+    }
+}
+
+#[test]
+fn separators_match_narrow_columns() {
+    let output = render("test/narrow_columns.md", &[]);
+    let table: Vec<&str> = output.lines().filter(|line| line.starts_with('|')).collect();
+    assert_eq!(table.len(), 3);
+    assert!(table.iter().all(|line| line.chars().count() == table[0].chars().count()), "{:?}", table);
+}
+
+#[test]
+fn tabs_in_table_cells_keep_the_columns_aligned() {
+    for args in [&[][..], &["--tab-size-for-tables", "8"]] {
+        let output = render("test/table_tabs.md", args);
+        let table: Vec<&str> = output.lines().filter(|line| line.starts_with('|')).collect();
+        assert_eq!(table.len(), 5);
+        assert!(table.iter().all(|line| !line.contains('\t')), "{:?}", table);
+        assert!(table.iter().all(|line| line.chars().count() == table[0].chars().count()), "{:?}", table);
+    }
+}
+
+#[test]
+fn watch_survives_a_failed_render() {
+    use std::{fs, process::Stdio, thread, time::Duration};
+
+    let path = std::env::temp_dir().join(format!("md-preview-watch-{}.md", std::process::id()));
+    fs::write(&path, "# Kept\n\nfirst\n").unwrap();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_md-preview"))
+        .args(["--no-color", "--since-heading", "Kept", "--watch"])
+        .arg(&path)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("md-preview runs");
+    // Each change is left time for a poll, which happens every half second
+    thread::sleep(Duration::from_millis(1000));
+    fs::write(&path, "no heading now\n").unwrap();
+    thread::sleep(Duration::from_millis(1200));
+    fs::write(&path, "# Kept\n\nsecond\n").unwrap();
+    thread::sleep(Duration::from_millis(1200));
+    let running = child.try_wait().unwrap().is_none();
+    child.kill().unwrap();
+    let output = child.wait_with_output().unwrap();
+    fs::remove_file(&path).ok();
+
+    assert!(running, "the watch ended: {}", String::from_utf8_lossy(&output.stderr));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("no heading matches --since-heading"));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("second"));
+}
+
+#[test]
+fn limit_bytes_keeps_what_fits() {
+    let output = md_preview(&["--to", "html", "--limit-bytes", "40", "test/setext.md"]);
+    let output = String::from_utf8(output.stdout).expect("UTF-8 output");
+    assert!(output.starts_with("<h1>Setext Title</h1>\n<h1>ATX"), "{:?}", output);
+    assert!(output.ends_with("\n… (output truncated)\n"));
+    assert!(!output.contains('\x1b'));
+
+    // Cut inside a multi-byte character, the character is left out whole
+    let full = render("test/utf16.md", &[]);
+    let cut = full.find('é').expect("an é in the fixture") + 1;
+    let output = md_preview(&["--no-color", "--limit-bytes", &cut.to_string(), "test/utf16.md"]);
+    let output = String::from_utf8(output.stdout).expect("no character split");
+    assert!(output.starts_with(&full[..cut - 1]));
+    assert!(output.ends_with(&format!("{}\n… (output truncated)\n", &full[cut - 4..cut - 1])));
+
+    // No hyperlink is open, so none is closed
+    let output = md_preview(&["--to", "ansi", "--limit-bytes", "20", "test/setext.md"]);
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("\x1b]8;;"));
+}